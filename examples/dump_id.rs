@@ -1,6 +1,5 @@
-use skitter_ro_client::CompressedWeb;
-use sqlx::Row;
-use sqlx::{migrate::MigrateDatabase, sqlite::SqlitePoolOptions};
+use skitter_ro_client::sqlx::{self, migrate::MigrateDatabase, sqlite::SqlitePoolOptions};
+use skitter_ro_client::{SqliteStorage, Storage};
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 1)]
 async fn main() {
@@ -26,28 +25,18 @@ async fn main() {
         .await
         .expect("failed to connect to sqlite db");
 
-    let row = match sqlx::query("select * from web where id = ?")
-        .bind(id)
-        .fetch_optional(&pool)
+    let storage = SqliteStorage::from_pool(pool)
         .await
-        .expect("failed to query db")
-    {
-        Some(row) => row,
-        None => panic!("failed to find id: {id}"),
-    };
+        .expect("failed to open sqlite db");
 
-    let compressed_web = CompressedWeb {
-        id: row.try_get("id").expect("failed to load id"),
-        created: row.try_get("created").expect("failed to load created"),
-        url: row.try_get("url").expect("failed to load url"),
-        status: row.try_get("status").expect("failed to load status"),
-        response: row.try_get("response").expect("failed to load response"),
+    let compressed_web = match storage.get(id).await.expect("failed to query db") {
+        Some(w) => w,
+        None => panic!("failed to find id: {id}"),
     };
     eprintln!("{compressed_web:?}");
 
     let web = compressed_web
-        .decompress()
-        .await
+        .decompress_sync()
         .expect("failed to decompress");
     eprintln!("          {web:?}");
 
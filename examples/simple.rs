@@ -1,23 +1,11 @@
-use skitter_ro_client::{Client, Url};
+use skitter_ro_client::{ClientBuilder, EnvCredentials};
 
 #[tokio::main]
 async fn main() {
-    let args = std::env::args().collect::<Vec<String>>();
-    if args.len() != 3 {
-        println!("usage: {} <user> <pass>", args[0]);
-        return;
-    }
-
-    let user = &args[1];
-    let pass = &args[2];
-
-    let client = reqwest::Client::new();
-    let client = Client::new(
-        client,
-        Url::parse("https://zst1uv23.fanfic.dev/").unwrap(),
-        user,
-        pass,
-    );
+    // Credentials are read from `SKITTER_RO_USER` and `SKITTER_RO_PASS`.
+    let client = ClientBuilder::from_credentials(EnvCredentials::default())
+        .build()
+        .expect("failed to build client");
 
     let res = client
         .fetch_range(148868250, 148868500, Some("%/s/%"))
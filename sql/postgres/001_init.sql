@@ -0,0 +1,7 @@
+create table if not exists web (
+	id int8 primary key,
+	created timestamptz not null,
+	url text not null,
+	status int2 not null,
+	response bytea not null
+);
@@ -0,0 +1,246 @@
+#[cfg(feature = "client")]
+use crate::Client;
+use crate::{CompressedWeb, Error, InvalidEntry, Web, WebStat, MAX_RANGE};
+
+// SkitterApi is the read-only API implemented by `Client`. Depend on it instead of `Client` to
+// substitute `MemoryApi` or another implementation in tests.
+#[async_trait::async_trait]
+pub trait SkitterApi: Send + Sync {
+    async fn fetch_stat(&self) -> Result<WebStat, Error>;
+
+    async fn fetch_range_compressed(
+        &self,
+        min_wid: i64,
+        max_wid: i64,
+        url_like: Option<&str>,
+    ) -> Result<Vec<CompressedWeb>, Error>;
+
+    // fetch_range_entries is like `fetch_range_compressed` but returns an `InvalidEntry` for
+    // each entry that could not be deserialized instead of dropping it.
+    async fn fetch_range_entries(
+        &self,
+        min_wid: i64,
+        max_wid: i64,
+        url_like: Option<&str>,
+    ) -> Result<Vec<Result<CompressedWeb, InvalidEntry>>, Error>;
+
+    async fn fetch_range(
+        &self,
+        min_wid: i64,
+        max_wid: i64,
+        url_like: Option<&str>,
+    ) -> Result<Vec<Web>, Error>;
+}
+
+#[cfg(feature = "client")]
+#[async_trait::async_trait]
+impl SkitterApi for Client {
+    async fn fetch_stat(&self) -> Result<WebStat, Error> {
+        Client::fetch_stat(self).await
+    }
+
+    async fn fetch_range_compressed(
+        &self,
+        min_wid: i64,
+        max_wid: i64,
+        url_like: Option<&str>,
+    ) -> Result<Vec<CompressedWeb>, Error> {
+        Client::fetch_range_compressed(self, min_wid, max_wid, url_like).await
+    }
+
+    async fn fetch_range_entries(
+        &self,
+        min_wid: i64,
+        max_wid: i64,
+        url_like: Option<&str>,
+    ) -> Result<Vec<Result<CompressedWeb, InvalidEntry>>, Error> {
+        Client::fetch_range_entries(self, min_wid, max_wid, url_like).await
+    }
+
+    async fn fetch_range(
+        &self,
+        min_wid: i64,
+        max_wid: i64,
+        url_like: Option<&str>,
+    ) -> Result<Vec<Web>, Error> {
+        Client::fetch_range(self, min_wid, max_wid, url_like).await
+    }
+}
+
+// MemoryApi serves a fixed set of `Web` entries the way the server would: ranges are half-open,
+// may span at most `MAX_RANGE` ids, and `url_like` is a case sensitive SQL `LIKE` pattern.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryApi {
+    webs: Vec<Web>,
+}
+
+impl MemoryApi {
+    pub fn new(mut webs: Vec<Web>) -> Self {
+        webs.sort_by_key(|w| w.id);
+        Self { webs }
+    }
+
+    fn range<'a>(
+        &'a self,
+        min_wid: i64,
+        max_wid: i64,
+        url_like: Option<&'a str>,
+    ) -> Result<impl Iterator<Item = &'a Web> + 'a, Error> {
+        if max_wid - min_wid > MAX_RANGE {
+            return Err(Error::Range { min_wid, max_wid });
+        }
+        Ok(self.webs.iter().filter(move |w| {
+            (min_wid..max_wid).contains(&w.id) && url_like.is_none_or(|p| like(&w.url, p))
+        }))
+    }
+}
+
+#[async_trait::async_trait]
+impl SkitterApi for MemoryApi {
+    // fetch_stat reports a `max_wid` of 0 when there are no entries.
+    async fn fetch_stat(&self) -> Result<WebStat, Error> {
+        Ok(WebStat {
+            max_wid: self.webs.last().map_or(0, |w| w.id),
+        })
+    }
+
+    async fn fetch_range_compressed(
+        &self,
+        min_wid: i64,
+        max_wid: i64,
+        url_like: Option<&str>,
+    ) -> Result<Vec<CompressedWeb>, Error> {
+        self.range(min_wid, max_wid, url_like)?
+            .map(|w| w.clone().compress())
+            .collect()
+    }
+
+    // fetch_range_entries never returns an `InvalidEntry`, since every entry is a valid `Web`.
+    async fn fetch_range_entries(
+        &self,
+        min_wid: i64,
+        max_wid: i64,
+        url_like: Option<&str>,
+    ) -> Result<Vec<Result<CompressedWeb, InvalidEntry>>, Error> {
+        let entries = self
+            .fetch_range_compressed(min_wid, max_wid, url_like)
+            .await?;
+        Ok(entries.into_iter().map(Ok).collect())
+    }
+
+    async fn fetch_range(
+        &self,
+        min_wid: i64,
+        max_wid: i64,
+        url_like: Option<&str>,
+    ) -> Result<Vec<Web>, Error> {
+        Ok(self.range(min_wid, max_wid, url_like)?.cloned().collect())
+    }
+}
+
+// like matches `s` against a SQL `LIKE` pattern: `%` matches any sequence, `_` any single
+// character and `\` escapes the next character.
+fn like(s: &str, pattern: &str) -> bool {
+    enum Token {
+        Any,
+        One,
+        Char(char),
+    }
+
+    let mut tokens = vec![];
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '%' => Token::Any,
+            '_' => Token::One,
+            '\\' => Token::Char(chars.next().unwrap_or('\\')),
+            c => Token::Char(c),
+        });
+    }
+
+    // matched[i] is whether the first `i` tokens match the input consumed so far.
+    let mut matched = vec![false; tokens.len() + 1];
+    matched[0] = true;
+    for (i, t) in tokens.iter().enumerate() {
+        matched[i + 1] = matched[i] && matches!(t, Token::Any);
+    }
+    for c in s.chars() {
+        let mut next = vec![false; tokens.len() + 1];
+        for (i, t) in tokens.iter().enumerate() {
+            next[i + 1] = match t {
+                Token::Any => matched[i + 1] || next[i],
+                Token::One => matched[i],
+                Token::Char(t) => matched[i] && *t == c,
+            };
+        }
+        matched = next;
+    }
+    matched[tokens.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::web_at;
+
+    fn ids(webs: &[Web]) -> Vec<i64> {
+        webs.iter().map(|w| w.id).collect()
+    }
+
+    #[test]
+    fn like_patterns() {
+        assert!(like("https://example.com/s/1/1", "%example.com/s/%"));
+        assert!(like(
+            "https://example.com/s/1/1",
+            "https://example.com/s/_/_"
+        ));
+        assert!(!like(
+            "https://example.com/s/12/1",
+            "https://example.com/s/_/_"
+        ));
+        assert!(!like("https://Example.com/", "%example%"));
+        assert!(like("100%", "100\\%"));
+        assert!(!like("1000", "100\\%"));
+        assert!(like("", "%"));
+        assert!(!like("", "_"));
+        assert!(like("abc", "a%%c"));
+    }
+
+    #[tokio::test]
+    async fn memory_api() {
+        let api = MemoryApi::new(vec![
+            web_at(102, "https://example.com/s/2/1"),
+            web_at(100, "https://example.com/s/1/1"),
+            web_at(101, "https://example.org/s/1/2"),
+            web_at(1100, "https://example.com/s/3/1"),
+        ]);
+
+        assert_eq!(api.fetch_stat().await.unwrap().max_wid, 1100);
+        assert_eq!(MemoryApi::default().fetch_stat().await.unwrap().max_wid, 0);
+
+        let res = api.fetch_range(100, 1100, None).await.unwrap();
+        assert_eq!(ids(&res), [100, 101, 102]);
+
+        let res = api
+            .fetch_range(0, 1000, Some("https://example.com/%"))
+            .await
+            .unwrap();
+        assert_eq!(ids(&res), [100, 102]);
+
+        let res = api.fetch_range_compressed(101, 102, None).await.unwrap();
+        assert_eq!(res.len(), 1);
+        let w = res.into_iter().next().unwrap().decompress_sync().unwrap();
+        assert_eq!(w, web_at(101, "https://example.org/s/1/2"));
+
+        let res = api.fetch_range(100, 1101, None).await;
+        assert!(matches!(res, Err(Error::Range { .. })));
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn client_is_skitter_api() {
+        fn assert_api<T: SkitterApi + 'static>(_: &T) {}
+
+        assert_api(&Client::builder("api_user", "api_pass").build().unwrap());
+    }
+}
@@ -0,0 +1,440 @@
+use crate::storage::last_per_id;
+use crate::{rfc3339, CompressedWeb, Error, Storage, Web, DEFAULT_MAX_DECOMPRESSED_SIZE};
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use time::OffsetDateTime;
+
+// IndexEntry is one line of a `BlobStore`'s `index.jsonl`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub id: i64,
+    #[serde(with = "rfc3339")]
+    pub created: OffsetDateTime,
+    pub url: String,
+    pub status: i16,
+    // hash is the hex encoded sha256 of the decompressed response.
+    pub hash: String,
+    // len is the decompressed response length in bytes.
+    pub len: u64,
+}
+
+// DedupStats summarizes how much a `BlobStore` saves by storing identical responses once.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    pub entries: u64,
+    pub blobs: u64,
+    // logical_bytes is the total length of every entry's response.
+    pub logical_bytes: u64,
+    // stored_bytes is the total length of the distinct blobs.
+    pub stored_bytes: u64,
+}
+
+impl DedupStats {
+    // ratio is `logical_bytes / stored_bytes`, or 1 when nothing is stored.
+    pub fn ratio(&self) -> f64 {
+        if self.stored_bytes == 0 {
+            return 1.0;
+        }
+        self.logical_bytes as f64 / self.stored_bytes as f64
+    }
+}
+
+// BlobStore is a filesystem `Storage` that keeps each distinct decompressed response once,
+// named by its sha256:
+//
+// ```text
+// <root>/index.jsonl          one `IndexEntry` per stored id
+// <root>/blobs/ab/cd/abcd...  decompressed responses
+// ```
+//
+// Blobs are written and synced before the index lines that reference them, and the index is
+// synced after each append, so a crash can at worst leave an unreferenced blob behind. An id
+// that is indexed again gets a new index line, and the last line for an id wins. `get` and
+// `range` compress responses again, so their `response` bytes may differ from the ones inserted
+// while decompressing to the same body.
+#[derive(Clone, Debug)]
+pub struct BlobStore {
+    inner: Arc<Inner>,
+    max_decompressed_size: usize,
+}
+
+#[derive(Debug)]
+struct Inner {
+    root: PathBuf,
+    index: Mutex<BTreeMap<i64, IndexEntry>>,
+    // writer is held by `insert` from comparing entries against `index` to updating it, so
+    // that concurrent inserts append to the index file in the order they update `index`.
+    writer: Mutex<()>,
+}
+
+impl BlobStore {
+    // open opens the store at `root`, creating it if needed.
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, Error> {
+        let root = root.into();
+        std::fs::create_dir_all(root.join("blobs")).map_err(|e| io_error(&root, e))?;
+        let index = read_index(&root.join("index.jsonl"))?;
+        Ok(Self {
+            inner: Arc::new(Inner {
+                root,
+                index: Mutex::new(index),
+                writer: Mutex::default(),
+            }),
+            max_decompressed_size: DEFAULT_MAX_DECOMPRESSED_SIZE,
+        })
+    }
+
+    // with_max_decompressed_size overrides `DEFAULT_MAX_DECOMPRESSED_SIZE` for inserted entries.
+    pub fn with_max_decompressed_size(mut self, max_decompressed_size: usize) -> Self {
+        self.max_decompressed_size = max_decompressed_size;
+        self
+    }
+
+    pub fn entry(&self, id: i64) -> Option<IndexEntry> {
+        self.inner.index.lock().unwrap().get(&id).cloned()
+    }
+
+    // blob_path is where the response with sha256 `hash` is stored.
+    pub fn blob_path(&self, hash: &str) -> PathBuf {
+        self.inner.blob_path(hash)
+    }
+
+    pub fn stats(&self) -> DedupStats {
+        let index = self.inner.index.lock().unwrap();
+        let mut blobs = HashMap::new();
+        let mut stats = DedupStats::default();
+        for e in index.values() {
+            stats.entries += 1;
+            stats.logical_bytes += e.len;
+            blobs.insert(e.hash.as_str(), e.len);
+        }
+        stats.blobs = blobs.len() as u64;
+        stats.stored_bytes = blobs.values().sum();
+        stats
+    }
+
+    // blocking runs `f` on tokio's blocking thread pool.
+    async fn blocking<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Inner) -> Result<T, Error> + Send + 'static,
+    ) -> Result<T, Error> {
+        let inner = self.inner.clone();
+        crate::spawn_blocking(move || f(&inner))
+            .await
+            .map_err(|e| Error::Storage(Box::new(e)))?
+    }
+}
+
+impl Inner {
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.root
+            .join("blobs")
+            .join(&hash[..2])
+            .join(&hash[2..4])
+            .join(hash)
+    }
+
+    // insert indexes the entries of `webs` that are new or differ from the indexed entry.
+    // `webs` must not contain an id twice.
+    fn insert(&self, webs: Vec<CompressedWeb>, max_decompressed_size: usize) -> Result<u64, Error> {
+        let _writer = self.writer.lock().unwrap();
+
+        let mut entries = vec![];
+        for w in webs {
+            let w = w.decompress_sync_with_limit(max_decompressed_size)?;
+            let hash = Sha256::digest(&w.response)
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>();
+            let e = IndexEntry {
+                id: w.id,
+                created: w.created,
+                url: w.url,
+                status: w.status,
+                hash,
+                len: w.response.len() as u64,
+            };
+            if self.index.lock().unwrap().get(&e.id) == Some(&e) {
+                continue;
+            }
+            self.write_blob(&e.hash, &w.response)?;
+            entries.push(e);
+        }
+        if entries.is_empty() {
+            return Ok(0);
+        }
+
+        let mut lines = vec![];
+        for e in entries.iter() {
+            serde_json::to_writer(&mut lines, e)?;
+            lines.push(b'\n');
+        }
+
+        let path = self.root.join("index.jsonl");
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut f| {
+                f.write_all(&lines)?;
+                f.sync_all()
+            })
+            .map_err(|e| io_error(&path, e))?;
+
+        let inserted = entries.len() as u64;
+        let mut index = self.index.lock().unwrap();
+        index.extend(entries.into_iter().map(|e| (e.id, e)));
+        Ok(inserted)
+    }
+
+    // write_blob stores `body` unless a blob with the same hash and length already exists, so
+    // that a blob left truncated by a crash is written again.
+    fn write_blob(&self, hash: &str, body: &[u8]) -> Result<(), Error> {
+        let path = self.blob_path(hash);
+        if std::fs::metadata(&path).is_ok_and(|m| m.len() == body.len() as u64) {
+            return Ok(());
+        }
+        let dir = path.parent().expect("blob paths have a parent");
+        std::fs::create_dir_all(dir).map_err(|e| io_error(dir, e))?;
+
+        let tmp = path.with_extension(format!("tmp.{}", std::process::id()));
+        std::fs::File::create(&tmp)
+            .and_then(|mut f| {
+                f.write_all(body)?;
+                f.sync_all()
+            })
+            .map_err(|e| io_error(&tmp, e))?;
+        std::fs::rename(&tmp, &path).map_err(|e| io_error(&path, e))
+    }
+
+    fn load(&self, e: IndexEntry) -> Result<CompressedWeb, Error> {
+        let path = self.blob_path(&e.hash);
+        let response = std::fs::read(&path).map_err(|err| io_error(&path, err))?;
+        if response.len() as u64 != e.len {
+            return Err(Error::Storage(
+                format!(
+                    "{}: expected {} bytes, got {}",
+                    path.display(),
+                    e.len,
+                    response.len()
+                )
+                .into(),
+            ));
+        }
+        Web {
+            id: e.id,
+            created: e.created,
+            url: e.url,
+            status: e.status,
+            response,
+        }
+        .compress()
+    }
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> Error {
+    Error::Storage(format!("{}: {e}", path.display()).into())
+}
+
+// read_index loads `index.jsonl`, where the last line for an id wins. A final line without a
+// newline is the remains of an interrupted append, so it is truncated away before later appends
+// could complete it.
+fn read_index(path: &Path) -> Result<BTreeMap<i64, IndexEntry>, Error> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(io_error(path, e)),
+    };
+
+    let complete = match contents.rfind('\n') {
+        Some(i) => &contents[..=i],
+        None => "",
+    };
+    if complete.len() < contents.len() {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(path)
+            .and_then(|f| f.set_len(complete.len() as u64))
+            .map_err(|e| io_error(path, e))?;
+    }
+
+    let mut index = BTreeMap::new();
+    for (n, line) in complete.lines().enumerate() {
+        let e: IndexEntry = serde_json::from_str(line)
+            .map_err(|e| Error::Storage(format!("{}:{}: {e}", path.display(), n + 1).into()))?;
+        index.insert(e.id, e);
+    }
+    Ok(index)
+}
+
+#[async_trait::async_trait]
+impl Storage for BlobStore {
+    // insert_batch decompresses each entry to compare it with the indexed one, so it fails on
+    // the first entry that cannot be decompressed without indexing any of the batch.
+    async fn insert_batch(&self, webs: &[CompressedWeb]) -> Result<u64, Error> {
+        let webs = last_per_id(webs).cloned().collect::<Vec<_>>();
+        let limit = self.max_decompressed_size;
+        self.blocking(move |inner| inner.insert(webs, limit)).await
+    }
+
+    async fn max_id(&self) -> Result<Option<i64>, Error> {
+        Ok(self.inner.index.lock().unwrap().keys().next_back().copied())
+    }
+
+    async fn get(&self, id: i64) -> Result<Option<CompressedWeb>, Error> {
+        match self.entry(id) {
+            Some(e) => self.blocking(move |inner| inner.load(e)).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn range(&self, min_wid: i64, max_wid: i64) -> BoxStream<'_, Result<CompressedWeb, Error>> {
+        let entries = self
+            .inner
+            .index
+            .lock()
+            .unwrap()
+            .range(min_wid..max_wid.max(min_wid))
+            .map(|(_, e)| e.clone())
+            .collect::<Vec<_>>();
+        stream::iter(entries)
+            .then(move |e| self.blocking(move |inner| inner.load(e)))
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{compressed, temp_path};
+    use futures::TryStreamExt;
+
+    #[tokio::test]
+    async fn dedup_and_reopen() {
+        let root = temp_path("blob_store");
+        let s = BlobStore::open(&root).unwrap();
+
+        let batch = [
+            compressed(100, "chapter 1"),
+            compressed(101, "chapter 1"),
+            compressed(102, "ch 2"),
+        ];
+        assert_eq!(s.insert_batch(&batch).await.unwrap(), 3);
+        assert_eq!(s.insert_batch(&batch).await.unwrap(), 0);
+
+        // sha256("chapter 1")
+        let hash = "a8a1339a8844c147c19bba544dbc3579d45484248b253668751ef96a5a5043ab";
+        assert_eq!(s.entry(100).unwrap().hash, hash);
+        assert_eq!(s.entry(101).unwrap().hash, hash);
+        assert_eq!(
+            s.blob_path(hash),
+            root.join("blobs")
+                .join(&hash[..2])
+                .join(&hash[2..4])
+                .join(hash)
+        );
+        assert_eq!(std::fs::read(s.blob_path(hash)).unwrap(), b"chapter 1");
+
+        let stats = s.stats();
+        assert_eq!(
+            stats,
+            DedupStats {
+                entries: 3,
+                blobs: 2,
+                logical_bytes: 22,
+                stored_bytes: 13,
+            }
+        );
+        assert!((stats.ratio() - 22.0 / 13.0).abs() < 1e-9);
+
+        // A torn final line is dropped on open,
+        let mut f = std::fs::OpenOptions::new()
+            .append(true)
+            .open(root.join("index.jsonl"))
+            .unwrap();
+        f.write_all(br#"{"id":103,"#).unwrap();
+
+        let s = BlobStore::open(&root).unwrap();
+        assert_eq!(s.max_id().await.unwrap(), Some(102));
+        // and does not corrupt the entries appended after it.
+        assert_eq!(s.insert_batch(&[compressed(103, "ch 3")]).await.unwrap(), 1);
+        // A changed entry replaces the indexed one, also after a reopen.
+        let batch = [compressed(101, "overwritten"), compressed(101, "changed")];
+        assert_eq!(s.insert_batch(&batch).await.unwrap(), 1);
+        let s = BlobStore::open(&root).unwrap();
+        assert_eq!(s.max_id().await.unwrap(), Some(103));
+        let w = s.get(101).await.unwrap().unwrap();
+        assert_eq!(w.decompress_sync().unwrap().response, b"changed");
+        let w = s.get(100).await.unwrap().unwrap();
+        assert_eq!(w.decompress_sync().unwrap().response, b"chapter 1");
+        assert!(s.get(104).await.unwrap().is_none());
+
+        let ids = s
+            .range(101, 200)
+            .map_ok(|w| w.id)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(ids, [101, 102, 103]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn concurrent_inserts_agree_after_reopen() {
+        let root = temp_path("blob_store_concurrent");
+        let s = BlobStore::open(&root).unwrap();
+
+        let inserts = (0..16).map(|i| {
+            let s = s.clone();
+            tokio::spawn(async move { s.insert_batch(&[compressed(100, &i.to_string())]).await })
+        });
+        for res in futures::future::join_all(inserts).await {
+            res.unwrap().unwrap();
+        }
+
+        let entry = s.entry(100).unwrap();
+        assert_eq!(BlobStore::open(&root).unwrap().entry(100).unwrap(), entry);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn truncated_blob() {
+        let root = temp_path("blob_store_truncated");
+        let s = BlobStore::open(&root).unwrap();
+        s.insert_batch(&[compressed(100, "chapter 1")])
+            .await
+            .unwrap();
+
+        let path = s.blob_path(&s.entry(100).unwrap().hash);
+        std::fs::write(&path, "chap").unwrap();
+        assert!(matches!(s.get(100).await, Err(Error::Storage(_))));
+
+        // Inserting the same body again repairs the blob.
+        s.insert_batch(&[compressed(101, "chapter 1")])
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"chapter 1");
+        let w = s.get(100).await.unwrap().unwrap();
+        assert_eq!(w.decompress_sync().unwrap().response, b"chapter 1");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn insert_decompress_error() {
+        let root = temp_path("blob_store_error");
+        let s = BlobStore::open(&root).unwrap();
+
+        let mut bad = compressed(101, "chapter 1");
+        bad.response.truncate(3);
+        let res = s.insert_batch(&[compressed(100, "chapter 1"), bad]).await;
+
+        assert!(matches!(res, Err(Error::Decompress(_))));
+        assert_eq!(s.max_id().await.unwrap(), None);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
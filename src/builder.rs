@@ -0,0 +1,246 @@
+use crate::{
+    Client, CredentialProvider, Error, RateLimit, RateLimiter, ReqwestTransport, RetryPolicy,
+    Secret, StaticCredentials, Transport, Url, DEFAULT_MAX_DECOMPRESSED_SIZE,
+};
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+use std::sync::Arc;
+use std::time::Duration;
+
+// DEFAULT_BASE_URL is the production read-only API.
+pub const DEFAULT_BASE_URL: &str = "https://zst1uv23.fanfic.dev/";
+
+// default_user_agent identifies this crate, its version and the api user. It is used unless
+// `ClientBuilder::user_agent` is set.
+pub fn default_user_agent(user: &str) -> String {
+    format!("skitter-ro-client-rs/{} +{user}", env!("CARGO_PKG_VERSION"))
+}
+
+// user_agent is `user_agent`, or `default_user_agent` when unset, followed by `suffix`.
+pub(crate) fn user_agent(user_agent: Option<&str>, suffix: Option<&str>, user: &str) -> String {
+    let user_agent = match user_agent {
+        Some(user_agent) => user_agent.to_string(),
+        None => default_user_agent(user),
+    };
+    match suffix {
+        Some(suffix) => format!("{user_agent} {suffix}"),
+        None => user_agent,
+    }
+}
+
+// ClientBuilder configures a `Client`, see `Client::builder`.
+//
+// `connect_timeout` and `proxy` configure the underlying `reqwest::Client` and are ignored when
+// one is injected with `client`, or when another `Transport` is used. Every other setting is
+// applied per request.
+#[derive(Debug)]
+pub struct ClientBuilder {
+    credentials: Arc<dyn CredentialProvider>,
+    base_url: Option<Url>,
+    transport: Option<Arc<dyn Transport>>,
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    user_agent: Option<String>,
+    user_agent_suffix: Option<String>,
+    default_headers: HeaderMap,
+    proxies: Vec<reqwest::Proxy>,
+    retry_policy: RetryPolicy,
+    rate_limit: Option<RateLimit>,
+    max_decompressed_size: usize,
+}
+
+impl ClientBuilder {
+    pub fn new(user: impl Into<String>, pass: impl Into<Secret>) -> Self {
+        Self::from_credentials(StaticCredentials::new(user, pass))
+    }
+
+    pub fn from_credentials(credentials: impl CredentialProvider + 'static) -> Self {
+        Self {
+            credentials: Arc::new(credentials),
+            base_url: None,
+            transport: None,
+            connect_timeout: None,
+            timeout: None,
+            user_agent: None,
+            user_agent_suffix: None,
+            default_headers: HeaderMap::new(),
+            proxies: vec![],
+            retry_policy: RetryPolicy::none(),
+            rate_limit: None,
+            max_decompressed_size: DEFAULT_MAX_DECOMPRESSED_SIZE,
+        }
+    }
+
+    // credentials replaces the provider consulted before each request.
+    pub fn credentials(mut self, credentials: impl CredentialProvider + 'static) -> Self {
+        self.credentials = Arc::new(credentials);
+        self
+    }
+
+    // base_url overrides `DEFAULT_BASE_URL`.
+    pub fn base_url(mut self, base_url: Url) -> Self {
+        self.base_url = Some(base_url);
+        self
+    }
+
+    // client injects a pre-built `reqwest::Client`.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.transport = Some(Arc::new(ReqwestTransport::new(client)));
+        self
+    }
+
+    // transport replaces the default `ReqwestTransport`.
+    pub fn transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    // timeout limits each request from sending it until its body has been read.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    // user_agent replaces `default_user_agent` entirely.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    // user_agent_suffix is appended, separated by a space, to the user agent.
+    pub fn user_agent_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.user_agent_suffix = Some(suffix.into());
+        self
+    }
+
+    // default_header is sent with every request.
+    pub fn default_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.default_headers.insert(name, value);
+        self
+    }
+
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxies.push(proxy);
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    pub fn max_decompressed_size(mut self, max_decompressed_size: usize) -> Self {
+        self.max_decompressed_size = max_decompressed_size;
+        self
+    }
+
+    // build_blocking builds a `blocking::Client`. The `transport`, `client` and `rate_limit`
+    // settings do not apply to it.
+    #[cfg(feature = "blocking")]
+    pub fn build_blocking(self) -> Result<crate::blocking::Client, Error> {
+        let base_url = match self.base_url {
+            Some(base_url) => base_url,
+            None => Url::parse(DEFAULT_BASE_URL)?,
+        };
+
+        let mut builder = reqwest::blocking::Client::builder();
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        for proxy in self.proxies {
+            builder = builder.proxy(proxy);
+        }
+
+        Ok(crate::blocking::Client {
+            client: builder.build().map_err(|e| Error::Build(Box::new(e)))?,
+            base_url,
+            credentials: self.credentials,
+            user_agent: self.user_agent,
+            user_agent_suffix: self.user_agent_suffix,
+            timeout: self.timeout,
+            default_headers: self.default_headers,
+            retry_policy: self.retry_policy,
+            max_decompressed_size: self.max_decompressed_size,
+        })
+    }
+
+    pub fn build(self) -> Result<Client, Error> {
+        let base_url = match self.base_url {
+            Some(base_url) => base_url,
+            None => Url::parse(DEFAULT_BASE_URL)?,
+        };
+
+        let transport = match self.transport {
+            Some(transport) => transport,
+            None => {
+                let mut builder = reqwest::Client::builder();
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+                for proxy in self.proxies {
+                    builder = builder.proxy(proxy);
+                }
+                Arc::new(ReqwestTransport::new(
+                    builder.build().map_err(|e| Error::Build(Box::new(e)))?,
+                ))
+            }
+        };
+
+        Ok(Client {
+            transport,
+            base_url,
+            credentials: self.credentials,
+            user_agent: self.user_agent,
+            user_agent_suffix: self.user_agent_suffix,
+            timeout: self.timeout,
+            default_headers: self.default_headers,
+            retry_policy: self.retry_policy,
+            rate_limiter: self.rate_limit.map(RateLimiter::new),
+            max_decompressed_size: self.max_decompressed_size,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_defaults() {
+        let client = ClientBuilder::new("api_user", "api_pass").build().unwrap();
+
+        assert_eq!(client.base_url.as_str(), DEFAULT_BASE_URL);
+        assert_eq!(
+            client.user_agent_for("api_user"),
+            format!(
+                "skitter-ro-client-rs/{} +api_user",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+        assert_eq!(client.timeout, None);
+        assert!(client.rate_limiter.is_none());
+    }
+
+    #[test]
+    fn build_user_agent() {
+        let client = ClientBuilder::new("api_user", "api_pass")
+            .user_agent("custom/1.0")
+            .user_agent_suffix("(+https://example.com)")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            client.user_agent_for("api_user"),
+            "custom/1.0 (+https://example.com)"
+        );
+    }
+}
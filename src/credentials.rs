@@ -0,0 +1,363 @@
+use crate::{Error, Secret};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use zeroize::Zeroizing;
+
+// Credentials are the basic auth user and password for the API.
+#[derive(Clone, Debug)]
+pub struct Credentials {
+    pub user: String,
+    pub pass: Secret,
+}
+
+impl Credentials {
+    pub fn new(user: impl Into<String>, pass: impl Into<Secret>) -> Self {
+        Self {
+            user: user.into(),
+            pass: pass.into(),
+        }
+    }
+}
+
+// CredentialProvider is consulted by `Client` before each request, so credentials can be rotated
+// without rebuilding the client.
+//
+// Providers are called from async code and should return quickly.
+pub trait CredentialProvider: fmt::Debug + Send + Sync {
+    fn credentials(&self) -> Result<Credentials, Error>;
+}
+
+// StaticCredentials always returns the same user and password.
+#[derive(Clone, Debug)]
+pub struct StaticCredentials(Credentials);
+
+impl StaticCredentials {
+    pub fn new(user: impl Into<String>, pass: impl Into<Secret>) -> Self {
+        Self(Credentials::new(user, pass))
+    }
+}
+
+impl CredentialProvider for StaticCredentials {
+    fn credentials(&self) -> Result<Credentials, Error> {
+        Ok(self.0.clone())
+    }
+}
+
+// EnvCredentials reads the user and password from environment variables, by default
+// `SKITTER_RO_USER` and `SKITTER_RO_PASS`.
+#[derive(Clone, Debug)]
+pub struct EnvCredentials {
+    pub user_var: String,
+    pub pass_var: String,
+}
+
+impl Default for EnvCredentials {
+    fn default() -> Self {
+        Self {
+            user_var: "SKITTER_RO_USER".to_string(),
+            pass_var: "SKITTER_RO_PASS".to_string(),
+        }
+    }
+}
+
+impl EnvCredentials {
+    // credentials_from looks the variables up with `lookup`, which is `std::env::var` outside
+    // of tests.
+    fn credentials_from(
+        &self,
+        lookup: impl Fn(&str) -> Result<String, std::env::VarError>,
+    ) -> Result<Credentials, Error> {
+        let var = |name: &str| {
+            lookup(name).map_err(|e| Error::Credentials(format!("{name}: {e}").into()))
+        };
+        Ok(Credentials::new(var(&self.user_var)?, var(&self.pass_var)?))
+    }
+}
+
+impl CredentialProvider for EnvCredentials {
+    fn credentials(&self) -> Result<Credentials, Error> {
+        self.credentials_from(|name| std::env::var(name))
+    }
+}
+
+// FileCredentials reads `user:pass` from the first line of a file. The file is only read again
+// once its modification time or length changes.
+#[derive(Clone, Debug)]
+pub struct FileCredentials {
+    pub path: PathBuf,
+    cache: FileCache,
+}
+
+impl FileCredentials {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            cache: FileCache::default(),
+        }
+    }
+}
+
+impl CredentialProvider for FileCredentials {
+    fn credentials(&self) -> Result<Credentials, Error> {
+        self.cache.get(&self.path, |contents| {
+            contents
+                .lines()
+                .next()
+                .and_then(|line| line.trim_end().split_once(':'))
+                .map(|(user, pass)| Credentials::new(user, pass))
+                .ok_or_else(|| {
+                    Error::Credentials(
+                        format!("{}: expected `user:pass`", self.path.display()).into(),
+                    )
+                })
+        })
+    }
+}
+
+// NetrcCredentials reads the `login` and `password` for `host` from a netrc file, falling back
+// to its `default` entry. Like `FileCredentials` it only reads the file again once it changes.
+#[derive(Clone, Debug)]
+pub struct NetrcCredentials {
+    pub path: PathBuf,
+    pub host: String,
+    cache: FileCache,
+}
+
+impl NetrcCredentials {
+    pub fn new(path: impl Into<PathBuf>, host: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            host: host.into(),
+            cache: FileCache::default(),
+        }
+    }
+
+    // for_host uses `$NETRC`, or `~/.netrc` when it is unset.
+    pub fn for_host(host: impl Into<String>) -> Result<Self, Error> {
+        let path = match std::env::var_os("NETRC") {
+            Some(path) => PathBuf::from(path),
+            None => match std::env::var_os("HOME") {
+                Some(home) => Path::new(&home).join(".netrc"),
+                None => return Err(Error::Credentials("HOME is not set".into())),
+            },
+        };
+        Ok(Self::new(path, host))
+    }
+}
+
+impl CredentialProvider for NetrcCredentials {
+    fn credentials(&self) -> Result<Credentials, Error> {
+        self.cache.get(&self.path, |contents| {
+            parse_netrc(contents, &self.host).ok_or_else(|| {
+                Error::Credentials(
+                    format!(
+                        "{}: no login and password for {}",
+                        self.path.display(),
+                        self.host
+                    )
+                    .into(),
+                )
+            })
+        })
+    }
+}
+
+// FileCache keeps the credentials parsed from a file along with the file's modification time
+// and length, so that a provider only stats the file while it is unchanged. It is shared by the
+// clones of a provider.
+#[derive(Clone, Debug, Default)]
+struct FileCache(Arc<Mutex<Option<CachedFile>>>);
+
+#[derive(Debug)]
+struct CachedFile {
+    path: PathBuf,
+    modified: SystemTime,
+    len: u64,
+    creds: Credentials,
+}
+
+impl FileCache {
+    // get returns the cached credentials for `path`, or reads and parses it again if it changed.
+    fn get(
+        &self,
+        path: &Path,
+        parse: impl FnOnce(&str) -> Result<Credentials, Error>,
+    ) -> Result<Credentials, Error> {
+        let (modified, len) = std::fs::metadata(path)
+            .and_then(|m| Ok((m.modified()?, m.len())))
+            .map_err(|e| io_error(path, e))?;
+
+        let mut cache = self.0.lock().unwrap();
+        if let Some(c) = cache.as_ref() {
+            if c.path == path && c.modified == modified && c.len == len {
+                return Ok(c.creds.clone());
+            }
+        }
+
+        let creds = parse(&read_to_string(path)?)?;
+        *cache = Some(CachedFile {
+            path: path.to_path_buf(),
+            modified,
+            len,
+            creds: creds.clone(),
+        });
+        Ok(creds)
+    }
+}
+
+fn read_to_string(path: &Path) -> Result<Zeroizing<String>, Error> {
+    std::fs::read_to_string(path)
+        .map(Zeroizing::new)
+        .map_err(|e| io_error(path, e))
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> Error {
+    Error::Credentials(format!("{}: {e}", path.display()).into())
+}
+
+fn parse_netrc(contents: &str, host: &str) -> Option<Credentials> {
+    let mut tokens = contents.split_whitespace();
+    let mut matched = None;
+    let mut default = None;
+    // entry is the entry currently being read: (is_match, login, password).
+    let mut entry: Option<(bool, Option<&str>, Option<&str>)> = None;
+
+    let mut finish = |entry: Option<(bool, Option<&str>, Option<&str>)>| {
+        if let Some((is_match, Some(login), Some(password))) = entry {
+            let slot = if is_match { &mut matched } else { &mut default };
+            slot.get_or_insert_with(|| Credentials::new(login, password));
+        }
+    };
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" => {
+                finish(entry.take());
+                let name = tokens.next()?;
+                entry = (name == host).then_some((true, None, None));
+            }
+            "default" => {
+                finish(entry.take());
+                entry = Some((false, None, None));
+            }
+            "login" => {
+                let v = tokens.next()?;
+                if let Some(e) = entry.as_mut() {
+                    e.1 = Some(v);
+                }
+            }
+            "password" => {
+                let v = tokens.next()?;
+                if let Some(e) = entry.as_mut() {
+                    e.2 = Some(v);
+                }
+            }
+            "account" => {
+                tokens.next()?;
+            }
+            _ => {}
+        }
+    }
+    finish(entry.take());
+
+    matched.or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = temp_path(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn parse_netrc_entries() {
+        let netrc = "
+            machine other.example.com login other password other_pass
+            default login anon password anon_pass
+            machine zst1uv23.fanfic.dev
+                login api_user
+                account ignored
+                password api_pass
+        ";
+
+        let c = parse_netrc(netrc, "zst1uv23.fanfic.dev").unwrap();
+        assert_eq!((c.user.as_str(), c.pass.expose()), ("api_user", "api_pass"));
+
+        let c = parse_netrc(netrc, "missing.example.com").unwrap();
+        assert_eq!((c.user.as_str(), c.pass.expose()), ("anon", "anon_pass"));
+
+        assert!(parse_netrc("machine a login b", "a").is_none());
+    }
+
+    #[test]
+    fn file_credentials() {
+        let path = temp_file("file_credentials", "api_user:api:pass\n");
+        let c = FileCredentials::new(&path).credentials().unwrap();
+        assert_eq!((c.user.as_str(), c.pass.expose()), ("api_user", "api:pass"));
+
+        std::fs::write(&path, "api_user\n").unwrap();
+        let res = FileCredentials::new(&path).credentials();
+        assert!(matches!(res, Err(Error::Credentials(_))));
+
+        // A changed file is read again, also through a clone sharing the cache.
+        let provider = FileCredentials::new(&path);
+        std::fs::write(&path, "api_user:old_pass\n").unwrap();
+        assert_eq!(provider.credentials().unwrap().pass.expose(), "old_pass");
+        assert_eq!(
+            provider.clone().credentials().unwrap().pass.expose(),
+            "old_pass"
+        );
+
+        std::fs::write(&path, "api_user:rotated_pass\n").unwrap();
+        assert_eq!(
+            provider.credentials().unwrap().pass.expose(),
+            "rotated_pass"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        let res = FileCredentials::new(&path).credentials();
+        assert!(matches!(res, Err(Error::Credentials(_))));
+    }
+
+    #[test]
+    fn netrc_credentials() {
+        let path = temp_file(
+            "netrc_credentials",
+            "machine example.com login api_user password api_pass\n",
+        );
+        let c = NetrcCredentials::new(&path, "example.com")
+            .credentials()
+            .unwrap();
+        assert_eq!((c.user.as_str(), c.pass.expose()), ("api_user", "api_pass"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn env_credentials() {
+        // The variables are looked up in a map, since setting them would race with the other
+        // tests reading the process environment.
+        let provider = EnvCredentials::default();
+        let mut vars = std::collections::HashMap::from([("SKITTER_RO_USER", "api_user")]);
+        let lookup = |vars: &std::collections::HashMap<_, &str>, name: &str| {
+            vars.get(name)
+                .map(|v| v.to_string())
+                .ok_or(std::env::VarError::NotPresent)
+        };
+
+        let res = provider.credentials_from(|name| lookup(&vars, name));
+        assert!(matches!(res, Err(Error::Credentials(_))));
+
+        vars.insert("SKITTER_RO_PASS", "api_pass");
+        let c = provider
+            .credentials_from(|name| lookup(&vars, name))
+            .unwrap();
+        assert_eq!((c.user.as_str(), c.pass.expose()), ("api_user", "api_pass"));
+    }
+}
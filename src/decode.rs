@@ -0,0 +1,271 @@
+use crate::{proto, CompressedWeb, Error, InvalidEntry};
+use serde::de::Error as _;
+
+// EntriesDecoder incrementally extracts the `entries` of a `/v0/web/range` response.
+//
+// Bytes are fed in as they arrive and every complete entry is deserialized as soon as it ends,
+// so only the entry currently being received is buffered. The envelope itself is only scanned
+// for structure; each entry is fully validated by serde_json, and an entry that fails validation
+// (including one that is not an object) is returned as an `InvalidEntry` without stopping the
+// decoder. Anything but whitespace after the envelope is an error.
+#[derive(Debug, Default)]
+pub struct EntriesDecoder {
+    buf: Vec<u8>,
+    // pos is the index in `buf` of the next byte to scan.
+    pos: usize,
+    depth: usize,
+    in_str: bool,
+    escaped: bool,
+    // expect_key is set while the next string in the top level object is a key.
+    expect_key: bool,
+    // key_start is the index in `buf` of the top level key currently being scanned.
+    key_start: Option<usize>,
+    last_key_is_entries: bool,
+    in_entries: bool,
+    seen_entries: bool,
+    // entry_start is the index in `buf` of the entry currently being scanned.
+    entry_start: Option<usize>,
+    // done is set once the top level object is closed, after which only whitespace may follow.
+    done: bool,
+}
+
+const ENTRIES_DEPTH: usize = 2;
+
+impl EntriesDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // feed scans `chunk` and returns every entry completed by it.
+    pub fn feed(
+        &mut self,
+        chunk: &[u8],
+    ) -> Result<Vec<Result<CompressedWeb, InvalidEntry>>, Error> {
+        self.buf.extend_from_slice(chunk);
+
+        let mut entries = vec![];
+        while self.pos < self.buf.len() {
+            let i = self.pos;
+            let c = self.buf[i];
+            self.pos += 1;
+
+            if self.in_str {
+                if self.escaped {
+                    self.escaped = false;
+                } else if c == b'\\' {
+                    self.escaped = true;
+                } else if c == b'"' {
+                    self.in_str = false;
+                    if let Some(start) = self.key_start.take() {
+                        self.last_key_is_entries = &self.buf[start..i] == b"entries";
+                    }
+                }
+                continue;
+            }
+
+            if c.is_ascii_whitespace() {
+                continue;
+            }
+            if self.depth == 0 && (self.done || c != b'{') {
+                let msg = if self.done {
+                    "trailing characters after json object"
+                } else {
+                    "expected a json object"
+                };
+                return Err(Error::Deserialize(serde_json::Error::custom(msg)));
+            }
+            // Every value directly inside `entries` is an entry, including ones that are not
+            // objects, so that they fail as an `InvalidEntry` like in `proto::parse_range`.
+            if self.depth == ENTRIES_DEPTH
+                && self.in_entries
+                && self.entry_start.is_none()
+                && !matches!(c, b',' | b']')
+            {
+                self.entry_start = Some(i);
+            }
+
+            match c {
+                b'"' => {
+                    self.in_str = true;
+                    if self.depth == 1 && self.expect_key {
+                        self.key_start = Some(i + 1);
+                        self.expect_key = false;
+                    }
+                }
+                b'{' | b'[' => {
+                    self.depth += 1;
+                    if self.depth == 1 {
+                        self.expect_key = true;
+                    } else if self.depth == ENTRIES_DEPTH && c == b'[' && self.last_key_is_entries {
+                        self.in_entries = true;
+                        self.seen_entries = true;
+                    }
+                }
+                b'}' | b']' => {
+                    if self.depth == 0 {
+                        return Err(Error::Deserialize(serde_json::Error::custom(
+                            "unbalanced json",
+                        )));
+                    }
+                    if self.depth == ENTRIES_DEPTH && self.in_entries {
+                        // A scalar entry ends at the end of `entries`.
+                        self.push_scalar(i, &mut entries);
+                        self.in_entries = false;
+                    }
+                    self.depth -= 1;
+                    if self.depth == ENTRIES_DEPTH && self.in_entries {
+                        if let Some(start) = self.entry_start.take() {
+                            entries.push(proto::parse_entry(&self.buf[start..=i]));
+                        }
+                    }
+                    self.done = self.depth == 0;
+                }
+                b',' if self.depth == ENTRIES_DEPTH && self.in_entries => {
+                    self.push_scalar(i, &mut entries);
+                }
+                b',' if self.depth == 1 => self.expect_key = true,
+                b':' if self.depth == 1 => self.expect_key = false,
+                _ => {}
+            }
+        }
+
+        self.compact();
+        Ok(entries)
+    }
+
+    // finish checks that the response was complete and contained an `entries` array.
+    pub fn finish(self) -> Result<(), Error> {
+        if self.depth != 0 || self.in_str {
+            return Err(Error::Deserialize(serde_json::Error::custom(
+                "unexpected end of response body",
+            )));
+        }
+        if !self.seen_entries {
+            return Err(Error::Deserialize(serde_json::Error::missing_field(
+                "entries",
+            )));
+        }
+        Ok(())
+    }
+
+    // push_scalar parses the scalar entry ending before the delimiter at `end`, if any.
+    fn push_scalar(&mut self, end: usize, entries: &mut Vec<Result<CompressedWeb, InvalidEntry>>) {
+        if let Some(start) = self.entry_start.take() {
+            entries.push(proto::parse_entry(self.buf[start..end].trim_ascii_end()));
+        }
+    }
+
+    // compact drops every scanned byte that is no longer needed.
+    fn compact(&mut self) {
+        let keep = match (self.entry_start, self.key_start) {
+            (Some(start), _) | (None, Some(start)) => start,
+            (None, None) => self.pos,
+        };
+        self.buf.drain(..keep);
+        self.pos -= keep;
+        self.entry_start = self.entry_start.map(|s| s - keep);
+        self.key_start = self.key_start.map(|s| s - keep);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &str = r#"{"meta":{"entries":[1]},"entries":[
+        {"id":100,"created":"2023-06-01T23:24:25.065Z","url":"https://example.com/s/{1}/\"1","status":200,"response":"AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"},
+        {"id":101,"created":"2023-06-02T23:24:25.065Z","url":"https://example.com/s/1/2]","status":200,"response":"AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"}
+    ],"other":[{"id":1}]}"#;
+
+    fn decode(
+        body: &[u8],
+        chunk_size: usize,
+    ) -> Result<Vec<Result<CompressedWeb, InvalidEntry>>, Error> {
+        let mut d = EntriesDecoder::new();
+        let mut res = vec![];
+        for chunk in body.chunks(chunk_size) {
+            res.extend(d.feed(chunk)?);
+        }
+        d.finish()?;
+        Ok(res)
+    }
+
+    #[test]
+    fn feed_any_chunk_size() {
+        for chunk_size in 1..=BODY.len() {
+            let res = decode(BODY.as_bytes(), chunk_size)
+                .unwrap()
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(res.len(), 2, "chunk_size: {chunk_size}");
+            assert_eq!(res[0].id, 100);
+            assert_eq!(res[0].url, r#"https://example.com/s/{1}/"1"#);
+            assert_eq!(res[1].id, 101);
+            assert_eq!(res[1].url, "https://example.com/s/1/2]");
+        }
+    }
+
+    #[test]
+    fn feed_empty_entries() {
+        assert_eq!(decode(br#"{"entries":[]}"#, 3).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn feed_buffers_only_current_entry() {
+        let mut d = EntriesDecoder::new();
+        let res = d.feed(&BODY.as_bytes()[..BODY.find("{\"id\":101").unwrap() + 5]);
+        assert_eq!(res.unwrap().len(), 1);
+        assert_eq!(d.buf, b"{\"id\"");
+    }
+
+    #[test]
+    fn feed_invalid_entry() {
+        let res = decode(
+            br#"{"entries":[{"id":"x"},{"id":7,"url":"u"},{"id":100,"created":"2023-06-01T23:24:25.065Z","url":"","status":200,"response":""}]}"#,
+            4,
+        )
+        .unwrap();
+        assert_eq!(res.len(), 3);
+        assert!(matches!(res[0], Err(InvalidEntry { id: None, .. })));
+        assert!(matches!(res[1], Err(InvalidEntry { id: Some(7), .. })));
+        assert_eq!(res[2].as_ref().unwrap().id, 100);
+    }
+
+    #[test]
+    fn finish_errors() {
+        assert!(matches!(
+            decode(br#"{"entries":[{"id":1"#, 4),
+            Err(Error::Deserialize(_))
+        ));
+        assert!(matches!(
+            decode(br#"{"other":[]}"#, 4),
+            Err(Error::Deserialize(_))
+        ));
+        assert!(matches!(decode(b"[]", 4), Err(Error::Deserialize(_))));
+    }
+
+    #[test]
+    fn feed_non_object_entries() {
+        for chunk_size in [1, 4, 100] {
+            let body = br#"{"entries":[null, {"id":1} ,5,"x",[1]]}"#;
+            let res = decode(body, chunk_size).unwrap();
+            assert_eq!(res.len(), 5, "chunk_size: {chunk_size}");
+            assert!(res.iter().all(|e| e.is_err()));
+            assert_eq!(res[1].as_ref().unwrap_err().id, Some(1));
+            assert_eq!(res.len(), proto::parse_range(body).unwrap().len());
+        }
+    }
+
+    #[test]
+    fn feed_trailing_data() {
+        let body = br#"{"entries":[]}{"entries":[{"id":1}]}"#;
+        assert!(proto::parse_range(body).is_err());
+        assert!(matches!(decode(body, 4), Err(Error::Deserialize(_))));
+        assert!(matches!(
+            decode(br#"x{"entries":[]}"#, 4),
+            Err(Error::Deserialize(_))
+        ));
+        assert_eq!(decode(b" {\"entries\":[]}\n", 4).unwrap().len(), 0);
+    }
+}
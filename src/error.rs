@@ -0,0 +1,163 @@
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+// Error is returned by every fallible operation in this crate.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to build full url: {0}")]
+    Url(#[from] url::ParseError),
+
+    #[error("failed to build http client: {0}")]
+    Build(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("checkpoint error: {0}")]
+    Checkpoint(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("failed to load credentials: {0}")]
+    Credentials(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("invalid header value: {0}")]
+    Header(#[from] http::header::InvalidHeaderValue),
+
+    // Transport and Body wrap the error of the `Transport` in use.
+    #[error("failed to send request: {0}")]
+    Transport(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("failed to fetch response body: {0}")]
+    Body(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("unexpected status: {status}: {}", describe_body(.api_error, .body))]
+    Status {
+        status: StatusCode,
+        // api_error is the parsed `Error` body, if the server sent one.
+        api_error: Option<ApiError>,
+        body: Vec<u8>,
+        // retry_after is the delay requested by the server's `Retry-After` header.
+        retry_after: Option<Duration>,
+    },
+
+    #[error("storage error: {0}")]
+    Storage(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("warc export error: {0}")]
+    Warc(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("failed to deserialize response body: {0}")]
+    Deserialize(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    InvalidEntry(#[from] InvalidEntry),
+
+    #[error("max_wid - min_wid must be less than {}", crate::MAX_RANGE)]
+    Range { min_wid: i64, max_wid: i64 },
+
+    #[error("compression error: {0}")]
+    Compress(#[source] std::io::Error),
+
+    #[error(transparent)]
+    Decompress(#[from] DecompressError),
+
+    #[error("failed to decompress response: {}", join(.0))]
+    DecompressAll(Vec<DecompressError>),
+}
+
+impl Error {
+    // status returns the http status of an unexpected non-200 response.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    // api_error returns the server's documented error body for a non-200 response.
+    pub fn api_error(&self) -> Option<&ApiError> {
+        match self {
+            Self::Status { api_error, .. } => api_error.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+// ApiError is the `Error` schema returned by the server alongside 400 and 500 responses.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiError {
+    pub err: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub msg: Option<String>,
+}
+
+impl ApiError {
+    // parse returns `None` when `body` is not a valid `Error` document.
+    pub fn parse(body: &[u8]) -> Option<Self> {
+        serde_json::from_slice(body).ok()
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.msg {
+            Some(msg) => write!(f, "error {}: {msg}", self.err),
+            None => write!(f, "error {}", self.err),
+        }
+    }
+}
+
+// DecompressError describes why a `CompressedWeb` response could not be decompressed.
+#[derive(Debug, thiserror::Error)]
+pub enum DecompressError {
+    #[error("decompression error: missing header, length: {len}")]
+    MissingHeader { len: usize },
+
+    #[error("decompression error: could not read to end: {0}")]
+    Read(#[source] std::io::Error),
+
+    #[error("decompression error: expected {expected} bytes, got {actual}")]
+    SizeMismatch { expected: usize, actual: usize },
+
+    #[error("decompression error: output exceeds expected {expected} bytes")]
+    ExceedsHeader { expected: usize },
+
+    #[error("decompression error: declared size {size} exceeds limit {limit}")]
+    TooLarge { size: usize, limit: usize },
+
+    // Cancelled is returned by `CompressedWeb::decompress` when the runtime shuts down first.
+    #[error("decompression error: cancelled by runtime shutdown")]
+    Cancelled,
+}
+
+// InvalidEntry identifies an entry of a `/v0/web/range` response that could not be deserialized
+// while the rest of the response could. `id` and `url` are set when they could be read.
+#[derive(Debug, thiserror::Error)]
+#[error("failed to deserialize entry{}: {error}", describe_entry(.id, .url))]
+pub struct InvalidEntry {
+    pub id: Option<i64>,
+    pub url: Option<String>,
+    #[source]
+    pub error: serde_json::Error,
+}
+
+fn describe_entry(id: &Option<i64>, url: &Option<String>) -> String {
+    match (id, url) {
+        (Some(id), Some(url)) => format!(" {id} ({url})"),
+        (Some(id), None) => format!(" {id}"),
+        (None, Some(url)) => format!(" ({url})"),
+        (None, None) => String::new(),
+    }
+}
+
+fn describe_body(api_error: &Option<ApiError>, body: &[u8]) -> String {
+    match api_error {
+        Some(e) => e.to_string(),
+        None => String::from_utf8_lossy(body).into_owned(),
+    }
+}
+
+fn join(errs: &[DecompressError]) -> String {
+    errs.iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
@@ -1,24 +1,140 @@
-use async_compression::tokio::bufread::ZlibDecoder;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+#[cfg(feature = "client")]
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+#[cfg(feature = "client")]
+use futures::TryFutureExt;
+pub use http;
+#[cfg(feature = "client")]
+use http::header::HeaderMap;
+#[cfg(feature = "client")]
 pub use reqwest;
-pub use reqwest::Url;
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::io::{Read, Write};
+#[cfg(feature = "client")]
+use std::sync::Arc;
+#[cfg(feature = "client")]
+use std::time::Duration;
 use time::OffsetDateTime;
-use tokio::io::AsyncReadExt;
+pub use url::Url;
+
+mod api;
+#[cfg(feature = "blob-store")]
+mod blob_store;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "client")]
+mod builder;
+mod credentials;
+mod decode;
+mod error;
+#[cfg(feature = "postgres")]
+mod postgres;
+pub mod proto;
+#[cfg(feature = "tokio")]
+mod rate_limit;
+mod replicate;
+mod retry;
+pub mod rfc3339;
+mod secret;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod storage;
+#[cfg(test)]
+mod test_util;
+#[cfg(feature = "client")]
+mod transport;
+#[cfg(feature = "warc")]
+pub mod warc;
+pub use api::{MemoryApi, SkitterApi};
+#[cfg(feature = "blob-store")]
+pub use blob_store::{BlobStore, DedupStats, IndexEntry};
+#[cfg(feature = "client")]
+pub use builder::{default_user_agent, ClientBuilder, DEFAULT_BASE_URL};
+pub use credentials::{
+    CredentialProvider, Credentials, EnvCredentials, FileCredentials, NetrcCredentials,
+    StaticCredentials,
+};
+pub use decode::EntriesDecoder;
+pub use error::{ApiError, DecompressError, Error, InvalidEntry};
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresStorage;
+#[cfg(feature = "tokio")]
+pub use rate_limit::{RateLimit, RateLimiter};
+pub use replicate::{
+    Block, CheckpointStore, FileCheckpointStore, MemoryCheckpointStore, Replicator,
+};
+pub use retry::RetryPolicy;
+pub use secret::Secret;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStorage;
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub use sqlx;
+pub use storage::Storage;
+#[cfg(feature = "client")]
+pub use transport::{Body, Request, ReqwestTransport, Response, Transport};
+
+// DEFAULT_MAX_DECOMPRESSED_SIZE is the default limit on the size of a decompressed response.
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 64 << 20;
+
+// MAX_RANGE is the largest `max_wid - min_wid` span accepted by `/v0/web/range`.
+pub const MAX_RANGE: i64 = 1000;
+
+// range_blocks splits the half-open range `[min_wid, max_wid)` into consecutive half-open blocks
+// no larger than `MAX_RANGE`.
+pub fn range_blocks(min_wid: i64, max_wid: i64) -> impl Iterator<Item = (i64, i64)> {
+    (min_wid..max_wid)
+        .step_by(MAX_RANGE as usize)
+        .map(move |lo| (lo, (lo + MAX_RANGE).min(max_wid)))
+}
 
+#[cfg(feature = "client")]
 #[derive(Clone)]
-pub struct Client<'a> {
-    pub client: reqwest::Client,
+pub struct Client {
+    // transport sends every request, see `ClientBuilder::transport`.
+    pub transport: Arc<dyn Transport>,
     pub base_url: Url,
-    pub user: &'a str,
-    pub pass: &'a str,
+    // credentials is consulted before each request.
+    pub credentials: Arc<dyn CredentialProvider>,
+    // user_agent replaces `default_user_agent`, see `ClientBuilder::user_agent`.
+    pub user_agent: Option<String>,
+    pub user_agent_suffix: Option<String>,
+    // timeout applies to each request, see `ClientBuilder::timeout`.
+    pub timeout: Option<Duration>,
+    pub default_headers: HeaderMap,
+    pub retry_policy: RetryPolicy,
+    // rate_limiter is shared by all clones of this client.
+    pub rate_limiter: Option<RateLimiter>,
+    // max_decompressed_size limits each response decompressed by `fetch_range`.
+    pub max_decompressed_size: usize,
+}
+
+// Client's `Debug` impl never prints credentials, see `Secret`.
+#[cfg(feature = "client")]
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("transport", &self.transport)
+            .field("base_url", &self.base_url.as_str())
+            .field("credentials", &self.credentials)
+            .field("user_agent", &self.user_agent)
+            .field("user_agent_suffix", &self.user_agent_suffix)
+            .field("timeout", &self.timeout)
+            .field("default_headers", &self.default_headers)
+            .field("retry_policy", &self.retry_policy)
+            .field("rate_limiter", &self.rate_limiter.is_some())
+            .field("max_decompressed_size", &self.max_decompressed_size)
+            .finish_non_exhaustive()
+    }
 }
 
 #[serde_with::serde_as]
-#[derive(Serialize, PartialEq)]
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
 pub struct Web {
     pub id: i64,
-    #[serde(serialize_with = "serialize_rfc3339")]
+    #[serde(with = "rfc3339")]
     pub created: OffsetDateTime,
     pub url: String,
     pub status: i16,
@@ -26,6 +142,31 @@ pub struct Web {
     pub response: Vec<u8>,
 }
 
+impl Web {
+    // compress produces the `CompressedWeb` wire format: a four byte big-endian decompressed size
+    // followed by the zlib compressed response.
+    pub fn compress(self) -> Result<CompressedWeb, Error> {
+        let size = u32::try_from(self.response.len()).map_err(|_| {
+            Error::Compress(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("response too large: {} bytes", self.response.len()),
+            ))
+        })?;
+
+        let mut e = ZlibEncoder::new(size.to_be_bytes().to_vec(), Compression::default());
+        e.write_all(&self.response).map_err(Error::Compress)?;
+        let response = e.finish().map_err(Error::Compress)?;
+
+        Ok(CompressedWeb {
+            id: self.id,
+            created: self.created,
+            url: self.url,
+            status: self.status,
+            response,
+        })
+    }
+}
+
 impl fmt::Debug for Web {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Web")
@@ -38,23 +179,11 @@ impl fmt::Debug for Web {
     }
 }
 
-fn serialize_rfc3339<S: Serializer>(v: &OffsetDateTime, s: S) -> Result<S::Ok, S::Error> {
-    s.serialize_str(
-        &v.format(&time::format_description::well_known::Rfc3339)
-            .unwrap(),
-    )
-}
-
-fn deserialize_rfc3339<'de, D: Deserializer<'de>>(d: D) -> Result<OffsetDateTime, D::Error> {
-    let s: &str = Deserialize::deserialize(d)?;
-    Ok(OffsetDateTime::parse(s, &time::format_description::well_known::Rfc3339).unwrap())
-}
-
 #[serde_with::serde_as]
-#[derive(Deserialize)]
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
 pub struct CompressedWeb {
     pub id: i64,
-    #[serde(deserialize_with = "deserialize_rfc3339")]
+    #[serde(with = "rfc3339")]
     pub created: OffsetDateTime,
     pub url: String,
     pub status: i16,
@@ -63,12 +192,37 @@ pub struct CompressedWeb {
 }
 
 impl CompressedWeb {
-    pub async fn decompress(self) -> Result<Web, String> {
+    // decompress runs `decompress_sync`, see `decompress_with_limit`.
+    #[cfg(feature = "tokio")]
+    pub async fn decompress(self) -> Result<Web, DecompressError> {
+        self.decompress_with_limit(DEFAULT_MAX_DECOMPRESSED_SIZE)
+            .await
+    }
+
+    // decompress_with_limit runs `decompress_sync_with_limit` on tokio's blocking thread pool
+    // when called from within a tokio runtime, and on the calling thread otherwise.
+    #[cfg(feature = "tokio")]
+    pub async fn decompress_with_limit(self, max_size: usize) -> Result<Web, DecompressError> {
+        if tokio::runtime::Handle::try_current().is_err() {
+            return self.decompress_sync_with_limit(max_size);
+        }
+        spawn_blocking(move || self.decompress_sync_with_limit(max_size))
+            .await
+            .unwrap_or(Err(DecompressError::Cancelled))
+    }
+
+    pub fn decompress_sync(self) -> Result<Web, DecompressError> {
+        self.decompress_sync_with_limit(DEFAULT_MAX_DECOMPRESSED_SIZE)
+    }
+
+    // decompress_sync_with_limit refuses to produce more than `max_size` bytes. Decompression
+    // stops as soon as the output exceeds the size declared in the header, so a corrupt or
+    // hostile payload can never inflate beyond `min(header, max_size)` bytes.
+    pub fn decompress_sync_with_limit(self, max_size: usize) -> Result<Web, DecompressError> {
         if self.response.len() < 4 {
-            return Err(format!(
-                "decompression error: missing header, length: {}",
-                self.response.len()
-            ));
+            return Err(DecompressError::MissingHeader {
+                len: self.response.len(),
+            });
         }
 
         let expected_size = {
@@ -79,17 +233,30 @@ impl CompressedWeb {
             u32::from_be_bytes(header) as usize
         };
 
-        let mut d = ZlibDecoder::new(&self.response[4..]);
-        let mut buf = Vec::<u8>::new();
-        if let Err(e) = d.read_to_end(&mut buf).await {
-            return Err(format!("decompression error: could not read to end: {e}"));
+        if expected_size > max_size {
+            return Err(DecompressError::TooLarge {
+                size: expected_size,
+                limit: max_size,
+            });
+        }
+
+        // Read at most one byte past the declared size to detect overlong output.
+        let mut d = ZlibDecoder::new(&self.response[4..]).take(expected_size as u64 + 1);
+        let mut buf = Vec::<u8>::with_capacity(expected_size);
+        if let Err(e) = d.read_to_end(&mut buf) {
+            return Err(DecompressError::Read(e));
         }
 
+        if buf.len() > expected_size {
+            return Err(DecompressError::ExceedsHeader {
+                expected: expected_size,
+            });
+        }
         if buf.len() != expected_size {
-            return Err(format!(
-                "decompression error: expected {expected_size} bytes, got {}",
-                buf.len()
-            ));
+            return Err(DecompressError::SizeMismatch {
+                expected: expected_size,
+                actual: buf.len(),
+            });
         }
 
         Ok(Web {
@@ -119,153 +286,380 @@ pub struct WebStat {
     pub max_wid: i64,
 }
 
-#[derive(Deserialize)]
-struct WebRangeResponse {
-    pub entries: Vec<CompressedWeb>,
+// FetchReport is the result of `Client::fetch_range_report`.
+#[derive(Debug, Default)]
+pub struct FetchReport {
+    pub webs: Vec<Web>,
+    pub failures: Vec<DecompressFailure>,
+    // invalid lists the entries that could not be deserialized.
+    pub invalid: Vec<InvalidEntry>,
 }
 
-impl<'a> Client<'a> {
-    pub fn new(client: reqwest::Client, base_url: Url, user: &'a str, pass: &'a str) -> Self {
-        Self {
-            client,
-            base_url,
+#[cfg(feature = "client")]
+impl FetchReport {
+    // take_invalid records the entries that could not be deserialized and returns the others.
+    fn take_invalid(
+        &mut self,
+        entries: Vec<Result<CompressedWeb, InvalidEntry>>,
+    ) -> Vec<CompressedWeb> {
+        let mut valid = vec![];
+        for e in entries {
+            match e {
+                Ok(w) => valid.push(w),
+                Err(e) => {
+                    warn_invalid(&e);
+                    self.invalid.push(e);
+                }
+            }
+        }
+        valid
+    }
+
+    // push_decompressed records the result of decompressing an entry.
+    fn push_decompressed(&mut self, res: Result<Web, DecompressFailure>) {
+        match res {
+            Ok(w) => self.webs.push(w),
+            Err(f) => {
+                tracing::warn!(id = f.id, url = f.url, error = %f.error, "failed to decompress");
+                self.failures.push(f);
+            }
+        }
+    }
+
+    // into_webs returns the decompressed responses, or `Error::DecompressAll` when there were
+    // entries but none of them could be decompressed.
+    fn into_webs(self) -> Result<Vec<Web>, Error> {
+        if self.webs.is_empty() && !self.failures.is_empty() {
+            return Err(Error::DecompressAll(
+                self.failures.into_iter().map(|f| f.error).collect(),
+            ));
+        }
+        Ok(self.webs)
+    }
+}
+
+// DecompressFailure identifies an entry that was fetched but could not be decompressed.
+#[derive(Debug)]
+pub struct DecompressFailure {
+    pub id: i64,
+    pub url: String,
+    pub error: DecompressError,
+}
+
+#[cfg(feature = "client")]
+impl Client {
+    pub fn new(
+        client: reqwest::Client,
+        base_url: Url,
+        user: impl Into<String>,
+        pass: impl Into<Secret>,
+    ) -> Self {
+        ClientBuilder::new(user, pass)
+            .client(client)
+            .base_url(base_url)
+            .build()
+            .expect("a client with an injected reqwest client and base url always builds")
+    }
+
+    // builder returns a `ClientBuilder` for the production API.
+    pub fn builder(user: impl Into<String>, pass: impl Into<Secret>) -> ClientBuilder {
+        ClientBuilder::new(user, pass)
+    }
+
+    // with_retry_policy makes `fetch_stat` and `fetch_range_compressed` retry transient failures.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    // with_max_decompressed_size overrides `DEFAULT_MAX_DECOMPRESSED_SIZE` for `fetch_range`.
+    pub fn with_max_decompressed_size(mut self, max_decompressed_size: usize) -> Self {
+        self.max_decompressed_size = max_decompressed_size;
+        self
+    }
+
+    // with_rate_limit throttles this client and all of its clones to `rate_limit`.
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limiter = Some(RateLimiter::new(rate_limit));
+        self
+    }
+
+    fn user_agent_for(&self, user: &str) -> String {
+        builder::user_agent(
+            self.user_agent.as_deref(),
+            self.user_agent_suffix.as_deref(),
             user,
-            pass,
+        )
+    }
+
+    // request builds an authenticated request for `url`, consulting `credentials`.
+    fn request(&self, url: Url) -> Result<Request, Error> {
+        let creds = self.credentials.credentials()?;
+        Ok(Request {
+            headers: proto::headers(
+                &self.default_headers,
+                &creds,
+                &self.user_agent_for(&creds.user),
+            )?,
+            url,
+            timeout: self.timeout,
+        })
+    }
+
+    // send sends a request for `url` and returns the response if its status is 200.
+    async fn send(&self, url: Url) -> Result<Response, Error> {
+        let req = self.request(url)?;
+        self.acquire().await;
+        let res = self.transport.send(req).await?;
+        if res.status != http::StatusCode::OK {
+            let body = res.body.bytes().await?;
+            return Err(proto::status_error(res.status, &res.headers, body));
         }
+        Ok(res)
     }
 
-    // stat returns information about the current state of the upstream db.
-    #[tracing::instrument(skip(self), err)]
-    pub async fn fetch_stat(&self) -> Result<WebStat, String> {
-        let res = self
-            .client
-            .get(
-                self.base_url
-                    .join("v0/web/stat")
-                    .map_err(|e| format!("failed to build full url: {e}"))?,
-            )
-            .basic_auth(self.user, Some(self.pass))
-            .header(
-                "User-Agent",
-                format!("skitter-ro-client-rs/0.0.1 +{}", self.user),
-            )
-            .send()
-            .await
-            .map_err(|e| format!("failed to send request: {e}"))?;
-
-        let status = res.status();
-        if status != reqwest::StatusCode::OK {
-            return match res.bytes().await {
-                Ok(body) => Err(format!(
-                    "failed to fetch web stat unexpected status: {status}: {body:?}",
-                )),
-                Err(e) => Err(format!(
-                    "failed to fetch web stat unexpected status and failed to read body: {status}: {e}",
-                )),
-            };
+    // send_bytes is `send` followed by reading the whole response body.
+    async fn send_bytes(&self, url: Url) -> Result<Vec<u8>, Error> {
+        let body = self.send(url).await?.body.bytes().await?;
+        self.consume_bytes(body.len());
+        Ok(body)
+    }
+
+    async fn acquire(&self) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
         }
+    }
 
-        let body = res
-            .text()
-            .await
-            .map_err(|e| format!("failed to fetch response body: {e}"))?;
-        serde_json::from_str::<WebStat>(&body)
-            .map_err(|e| format!("failed to deserialize response body: {e}"))
+    fn consume_bytes(&self, n: usize) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.consume_bytes(n);
+        }
+    }
+
+    // stat returns information about the current state of the upstream db.
+    #[tracing::instrument(skip(self), fields(attempts), err)]
+    pub async fn fetch_stat(&self) -> Result<WebStat, Error> {
+        self.retry_policy.run(|| self.fetch_stat_once()).await
+    }
+
+    async fn fetch_stat_once(&self) -> Result<WebStat, Error> {
+        let url = proto::stat_url(&self.base_url)?;
+        proto::parse_stat(&self.send_bytes(url).await?)
     }
 
     // fetch_range_compressed returns a range of cached web responses from based on their id.
-    #[tracing::instrument(skip(self), err)]
+    //
+    // Entries that cannot be deserialized are logged and dropped; use `fetch_range_entries` to
+    // get them.
     pub async fn fetch_range_compressed(
         &self,
         min_wid: i64,
         max_wid: i64,
         url_like: Option<&str>,
-    ) -> Result<Vec<CompressedWeb>, String> {
-        if max_wid - min_wid > 1000 {
-            return Err("max_wid - min_wid must be less than 1000".to_string());
-        }
+    ) -> Result<Vec<CompressedWeb>, Error> {
+        let entries = self.fetch_range_entries(min_wid, max_wid, url_like).await?;
+        Ok(drop_invalid(entries))
+    }
 
-        let params = [
-            ("min_wid", Some(min_wid.to_string())),
-            ("max_wid", Some(max_wid.to_string())),
-            ("url_like", url_like.map(|u| u.to_string())),
-        ];
-        let res = self
-            .client
-            .get(
-                self.base_url
-                    .join("v0/web/range")
-                    .map_err(|e| format!("failed to build full url: {e}"))?,
-            )
-            .query(&params)
-            .basic_auth(self.user, Some(self.pass))
-            .header(
-                "User-Agent",
-                format!("skitter-ro-client-rs/0.0.1 +{}", self.user),
-            )
-            .send()
+    // fetch_range_entries is like `fetch_range_compressed` but returns an `InvalidEntry` for
+    // each entry that cannot be deserialized.
+    #[tracing::instrument(skip(self), fields(attempts), err)]
+    pub async fn fetch_range_entries(
+        &self,
+        min_wid: i64,
+        max_wid: i64,
+        url_like: Option<&str>,
+    ) -> Result<Vec<Result<CompressedWeb, InvalidEntry>>, Error> {
+        let url = proto::range_url(&self.base_url, min_wid, max_wid, url_like)?;
+        self.retry_policy
+            .run(|| async { proto::parse_range(&self.send_bytes(url.clone()).await?) })
             .await
-            .map_err(|e| format!("failed to send request: {e}"))?;
-
-        let status = res.status();
-        if status != reqwest::StatusCode::OK {
-            return match res.bytes().await {
-                Ok(body) => Err(format!(
-                    "failed to fetch web range unexpected status: {status}: {body:?}",
-                )),
-                Err(e) => Err(format!(
-                    "failed to fetch web range unexpected status and failed to read body: {status}: {e}",
-                )),
-            };
-        }
+    }
 
-        let body = res
-            .text()
-            .await
-            .map_err(|e| format!("failed to fetch response body: {e}"))?;
-        Ok(serde_json::from_str::<WebRangeResponse>(&body)
-            .map_err(|e| format!("failed to deserialize response body: {e}"))?
-            .entries)
+    // fetch_range_stream is like `fetch_range_compressed` but decodes the response body as it is
+    // received, yielding each entry as soon as it is complete instead of buffering the whole
+    // block. Only sending the request is retried; the stream ends after the first error, except
+    // for entries that cannot be deserialized, which are yielded as `Error::InvalidEntry`.
+    #[tracing::instrument(skip(self), fields(attempts), err)]
+    pub async fn fetch_range_stream(
+        &self,
+        min_wid: i64,
+        max_wid: i64,
+        url_like: Option<&str>,
+    ) -> Result<impl Stream<Item = Result<CompressedWeb, Error>> + 'static, Error> {
+        let url = proto::range_url(&self.base_url, min_wid, max_wid, url_like)?;
+        let res = self.retry_policy.run(|| self.send(url.clone())).await?;
+
+        let limiter = self.rate_limiter.clone();
+        Ok(stream::try_unfold(
+            (res.body, Some(EntriesDecoder::new())),
+            move |(mut body, mut decoder)| {
+                let limiter = limiter.clone();
+                async move {
+                    let Some(d) = decoder.as_mut() else {
+                        return Ok(None);
+                    };
+                    let entries = match body.next().await {
+                        Some(chunk) => {
+                            let chunk = chunk?;
+                            if let Some(limiter) = &limiter {
+                                limiter.consume_bytes(chunk.len());
+                            }
+                            d.feed(&chunk)?
+                        }
+                        None => {
+                            decoder.take().unwrap().finish()?;
+                            vec![]
+                        }
+                    };
+                    Ok::<_, Error>(Some((
+                        stream::iter(entries.into_iter().map(|e| e.map_err(Error::from))),
+                        (body, decoder),
+                    )))
+                }
+            },
+        )
+        .try_flatten())
     }
 
     // fetch_range returns a range of cached web responses from based on their id.
+    //
+    // Entries that cannot be deserialized are dropped, as are entries that fail to decompress
+    // unless every entry failed; use `fetch_range_report` to find out which ones.
     #[tracing::instrument(skip(self), err)]
     pub async fn fetch_range(
         &self,
         min_wid: i64,
         max_wid: i64,
         url_like: Option<&str>,
-    ) -> Result<Vec<Web>, String> {
-        let entries = self
-            .fetch_range_compressed(min_wid, max_wid, url_like)
-            .await?;
-
-        let mut res = vec![];
-        let mut errs = vec![];
-        for w in entries.into_iter() {
-            match w.decompress().await {
-                Ok(w) => res.push(w),
-                Err(e) => errs.push(e),
-            }
-        }
+    ) -> Result<Vec<Web>, Error> {
+        self.fetch_range_report(min_wid, max_wid, url_like)
+            .await?
+            .into_webs()
+    }
 
-        if res.is_empty() && !errs.is_empty() {
-            return Err(format!(
-                "failed to decompress response: {}",
-                errs.join("; ")
-            ));
+    // fetch_range_report returns a range of decompressed web responses along with the id, url
+    // and error of every entry that could not be deserialized or decompressed.
+    #[tracing::instrument(skip(self), err)]
+    pub async fn fetch_range_report(
+        &self,
+        min_wid: i64,
+        max_wid: i64,
+        url_like: Option<&str>,
+    ) -> Result<FetchReport, Error> {
+        let mut report = FetchReport::default();
+        let entries =
+            report.take_invalid(self.fetch_range_entries(min_wid, max_wid, url_like).await?);
+
+        // Decompress about one entry per core at a time rather than starting a blocking task
+        // for every entry of the block at once.
+        let parallelism = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let decompressed = stream::iter(entries)
+            .map(|w| {
+                let (id, url) = (w.id, w.url.clone());
+                w.decompress_with_limit(self.max_decompressed_size)
+                    .map_err(move |error| DecompressFailure { id, url, error })
+            })
+            .buffered(parallelism)
+            .collect::<Vec<_>>()
+            .await;
+
+        for w in decompressed {
+            report.push_decompressed(w);
         }
-        Ok(res)
+        Ok(report)
     }
+
+    // stream_range yields the cached web responses in `[min_wid, max_wid)` in ascending order,
+    // fetching them in `MAX_RANGE` sized blocks as the stream is polled. The stream ends after
+    // the first error so that a failed block is never silently skipped, except for entries that
+    // cannot be deserialized, which are yielded as `Error::InvalidEntry`.
+    pub fn stream_range<'s>(
+        &'s self,
+        min_wid: i64,
+        max_wid: i64,
+        url_like: Option<&'s str>,
+    ) -> impl Stream<Item = Result<CompressedWeb, Error>> + 's {
+        flatten_blocks(
+            stream::iter(range_blocks(min_wid, max_wid))
+                .then(move |(lo, hi)| self.fetch_range_entries(lo, hi, url_like)),
+        )
+    }
+
+    // stream_range_parallel behaves like `stream_range` but keeps up to `concurrency` blocks in
+    // flight. Entries are still yielded in ascending order, and no new blocks are requested
+    // while `concurrency` fetched blocks are waiting to be consumed.
+    pub fn stream_range_parallel<'s>(
+        &'s self,
+        min_wid: i64,
+        max_wid: i64,
+        url_like: Option<&'s str>,
+        concurrency: usize,
+    ) -> impl Stream<Item = Result<CompressedWeb, Error>> + 's {
+        flatten_blocks(
+            stream::iter(range_blocks(min_wid, max_wid))
+                .map(move |(lo, hi)| self.fetch_range_entries(lo, hi, url_like))
+                .buffered(concurrency.max(1)),
+        )
+    }
+}
+
+// flatten_blocks flattens a stream of fetched blocks into their entries sorted by id. The
+// returned stream ends after the first failed block, dropping any blocks still in flight.
+#[cfg(feature = "client")]
+fn flatten_blocks<'s>(
+    blocks: impl Stream<Item = Result<Vec<Result<CompressedWeb, InvalidEntry>>, Error>> + 's,
+) -> impl Stream<Item = Result<CompressedWeb, Error>> + 's {
+    stream::try_unfold(Box::pin(blocks), |mut blocks| async move {
+        match blocks.next().await {
+            Some(entries) => {
+                let mut entries = entries?;
+                entries.sort_by_key(|e| match e {
+                    Ok(w) => Some(w.id),
+                    Err(e) => e.id,
+                });
+                let entries = entries.into_iter().map(|e| e.map_err(Error::from));
+                Ok::<_, Error>(Some((stream::iter(entries), blocks)))
+            }
+            None => Ok(None),
+        }
+    })
+    .try_flatten()
+}
+
+// drop_invalid logs and drops the entries that could not be deserialized.
+#[cfg(feature = "client")]
+fn drop_invalid(entries: Vec<Result<CompressedWeb, InvalidEntry>>) -> Vec<CompressedWeb> {
+    entries
+        .into_iter()
+        .filter_map(|e| e.map_err(|e| warn_invalid(&e)).ok())
+        .collect()
+}
+
+#[cfg(feature = "client")]
+fn warn_invalid(e: &InvalidEntry) {
+    tracing::warn!(id = e.id, url = e.url, error = %e.error, "failed to deserialize");
+}
+
+// spawn_blocking runs `f` on tokio's blocking thread pool, resuming its panic on the caller. It
+// fails when the runtime shut down before `f` ran.
+#[cfg(feature = "tokio")]
+pub(crate) async fn spawn_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, tokio::task::JoinError> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| match e.try_into_panic() {
+            Ok(panic) => std::panic::resume_unwind(panic),
+            Err(e) => e,
+        })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    const USER: &str = "api_user";
-    const PASS: &str = "api_pass";
-
     // A properly encoded `response` for testing can be produced through zlib and a base64 encoder:
     // ```python
     //     import zlib
@@ -276,11 +670,131 @@ mod tests {
     //     response = base64.b64encode(header + body)
     //     print(response)
     // ```
+    // or in rust with `Web::compress`.
 
-    fn parse_rfc3339(v: &str) -> OffsetDateTime {
+    pub(super) fn parse_rfc3339(v: &str) -> OffsetDateTime {
         OffsetDateTime::parse(v, &time::format_description::well_known::Rfc3339).unwrap()
     }
 
+    #[test]
+    fn range_blocks_split() {
+        assert_eq!(range_blocks(100, 100).collect::<Vec<_>>(), vec![]);
+        assert_eq!(
+            range_blocks(100, 1100).collect::<Vec<_>>(),
+            vec![(100, 1100)]
+        );
+        assert_eq!(
+            range_blocks(100, 2150).collect::<Vec<_>>(),
+            vec![(100, 1100), (1100, 2100), (2100, 2150)]
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn decompress_with_limit() {
+        use base64::Engine;
+
+        let compressed = || CompressedWeb {
+            id: 100,
+            created: parse_rfc3339("2023-06-01T23:24:25.065Z"),
+            url: "https://example.com/s/1/1".to_string(),
+            status: 200,
+            response: base64::engine::general_purpose::STANDARD
+                .decode("AAAADHicS61IzC3ISVVIyk+pBAAfFwS7")
+                .unwrap(),
+        };
+
+        let res = compressed().decompress_with_limit(12).await;
+        assert_eq!(res.unwrap().response, b"example body");
+
+        let res = compressed().decompress_with_limit(11).await;
+        assert!(matches!(
+            res,
+            Err(DecompressError::TooLarge {
+                size: 12,
+                limit: 11
+            })
+        ));
+    }
+
+    #[test]
+    fn decompress_sync() {
+        use base64::Engine;
+
+        let compressed = CompressedWeb {
+            id: 100,
+            created: parse_rfc3339("2023-06-01T23:24:25.065Z"),
+            url: "https://example.com/s/1/1".to_string(),
+            status: 200,
+            response: base64::engine::general_purpose::STANDARD
+                .decode("AAAADHicS61IzC3ISVVIyk+pBAAfFwS7")
+                .unwrap(),
+        };
+
+        let res = compressed.clone().decompress_sync();
+        assert_eq!(res.unwrap().response, b"example body");
+
+        // The async variant also works outside of a tokio runtime.
+        #[cfg(feature = "tokio")]
+        {
+            let res = futures::executor::block_on(compressed.decompress());
+            assert_eq!(res.unwrap().response, b"example body");
+        }
+    }
+
+    #[test]
+    fn compress_round_trip() {
+        use base64::Engine;
+
+        let web = || Web {
+            id: 100,
+            created: parse_rfc3339("2023-06-01T23:24:25.065Z"),
+            url: "https://example.com/s/1/1".to_string(),
+            status: 200,
+            response: b"example body".to_vec(),
+        };
+
+        let compressed = web().compress().unwrap();
+        assert_eq!(
+            base64::engine::general_purpose::STANDARD.encode(&compressed.response),
+            "AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"
+        );
+        assert_eq!(compressed.decompress_sync().unwrap(), web());
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let json = r#"{"id":100,"created":"2023-06-01T23:24:25.065Z","url":"https://example.com/s/1/1","status":200,"response":"AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"}"#;
+
+        let compressed = serde_json::from_str::<CompressedWeb>(json).unwrap();
+        assert_eq!(serde_json::to_string(&compressed).unwrap(), json);
+        assert_eq!(
+            serde_json::from_reader::<_, CompressedWeb>(json.as_bytes()).unwrap(),
+            compressed
+        );
+
+        let web = compressed.decompress_sync().unwrap();
+        let web_json = serde_json::to_string(&web).unwrap();
+        assert_eq!(
+            web_json,
+            r#"{"id":100,"created":"2023-06-01T23:24:25.065Z","url":"https://example.com/s/1/1","status":200,"response":"ZXhhbXBsZSBib2R5"}"#
+        );
+        assert_eq!(serde_json::from_str::<Web>(&web_json).unwrap(), web);
+        assert_eq!(
+            serde_json::from_reader::<_, Web>(web_json.as_bytes()).unwrap(),
+            web
+        );
+    }
+}
+
+#[cfg(all(test, feature = "client"))]
+mod client_tests {
+    use super::tests::parse_rfc3339;
+    use super::*;
+
+    const USER: &str = "api_user";
+    const PASS: &str = "api_pass";
+
     fn basic_auth(user: &str, pass: &str) -> String {
         use base64::Engine;
 
@@ -291,7 +805,7 @@ mod tests {
     }
 
     fn user_agent(user: &str) -> String {
-        format!("skitter-ro-client-rs/0.0.1 +{user}")
+        format!("skitter-ro-client-rs/{} +{user}", env!("CARGO_PKG_VERSION"))
     }
 
     #[tokio::test]
@@ -400,10 +914,16 @@ mod tests {
 
         let res = client.fetch_range(100, 1200, None).await;
 
-        assert!(res.is_err());
-        assert_eq!(
+        assert!(matches!(
             res,
-            Err("max_wid - min_wid must be less than 1000".to_string())
+            Err(Error::Range {
+                min_wid: 100,
+                max_wid: 1200
+            })
+        ));
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "max_wid - min_wid must be less than 1000"
         );
     }
 
@@ -421,7 +941,9 @@ mod tests {
 
         assert!(res.is_err());
         if let Err(e) = res {
-            assert!(e.starts_with("failed to send request: "));
+            assert!(matches!(e, Error::Transport(_)));
+            assert!(e.to_string().starts_with("failed to send request: "));
+            assert!(std::error::Error::source(&e).is_some());
         }
     }
 
@@ -449,7 +971,125 @@ mod tests {
         web_range_mock.assert();
 
         assert!(res.is_err());
-        assert_eq!(res, Err(r#"failed to fetch web range unexpected status: 500 Internal Server Error: b"{\"err\":1,\"msg\":\"internal server error\"}""#.to_string()));
+        let e = res.unwrap_err();
+        assert_eq!(e.status(), Some(reqwest::StatusCode::INTERNAL_SERVER_ERROR));
+        assert_eq!(
+            e.api_error(),
+            Some(&ApiError {
+                err: 1,
+                msg: Some("internal server error".to_string())
+            })
+        );
+        assert_eq!(
+            e.to_string(),
+            "unexpected status: 500 Internal Server Error: error 1: internal server error"
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_stat_error_unparseable_error_body() {
+        let server = httpmock::MockServer::start();
+        let web_stat_mock = server.mock(|when, then| {
+            when.method(httpmock::Method::GET).path("/v0/web/stat");
+            then.status(502)
+                .header("Content-Type", "text/html")
+                .body("<html>bad gateway</html>");
+        });
+
+        let base_url = Url::parse(&server.base_url()).unwrap();
+        let client = reqwest::Client::new();
+        let client = super::Client::new(client, base_url, USER, PASS);
+
+        let res = client.fetch_stat().await;
+
+        web_stat_mock.assert();
+
+        let e = res.unwrap_err();
+        assert_eq!(e.status(), Some(reqwest::StatusCode::BAD_GATEWAY));
+        assert_eq!(e.api_error(), None);
+        assert_eq!(
+            e.to_string(),
+            "unexpected status: 502 Bad Gateway: <html>bad gateway</html>"
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_range_retry_exhausted() {
+        let server = httpmock::MockServer::start();
+        let web_range_mock = server.mock(|when, then| {
+            when.method(httpmock::Method::GET).path("/v0/web/range");
+            then.status(503)
+                .header("Content-Type", "application/json")
+                .header("Retry-After", "0")
+                .body(r#"{"err":2,"msg":"unavailable"}"#);
+        });
+
+        let base_url = Url::parse(&server.base_url()).unwrap();
+        let client = reqwest::Client::new();
+        let client =
+            super::Client::new(client, base_url, USER, PASS).with_retry_policy(RetryPolicy {
+                max_attempts: 3,
+                ..RetryPolicy::default()
+            });
+
+        let res = client.fetch_range_compressed(100, 200, None).await;
+
+        web_range_mock.assert_hits(3);
+        assert_eq!(
+            res.unwrap_err().status(),
+            Some(reqwest::StatusCode::SERVICE_UNAVAILABLE)
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_stat_retry_not_retryable() {
+        let server = httpmock::MockServer::start();
+        let web_stat_mock = server.mock(|when, then| {
+            when.method(httpmock::Method::GET).path("/v0/web/stat");
+            then.status(400)
+                .header("Content-Type", "application/json")
+                .body(r#"{"err":3}"#);
+        });
+
+        let base_url = Url::parse(&server.base_url()).unwrap();
+        let client = reqwest::Client::new();
+        let client = super::Client::new(client, base_url, USER, PASS)
+            .with_retry_policy(RetryPolicy::default());
+
+        let res = client.fetch_stat().await;
+
+        web_stat_mock.assert_hits(1);
+        assert_eq!(
+            res.unwrap_err().status(),
+            Some(reqwest::StatusCode::BAD_REQUEST)
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_stat_rate_limit_shared_by_clones() {
+        let server = httpmock::MockServer::start();
+        let web_stat_mock = server.mock(|when, then| {
+            when.method(httpmock::Method::GET).path("/v0/web/stat");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .body(r#"{"max_wid":1024}"#);
+        });
+
+        let base_url = Url::parse(&server.base_url()).unwrap();
+        let client = reqwest::Client::new();
+        let client = super::Client::new(client, base_url, USER, PASS)
+            .with_rate_limit(RateLimit::requests_per_sec(4.0));
+        let clones = [client.clone(), client.clone()];
+
+        let start = std::time::Instant::now();
+        for _ in 0..3 {
+            for c in clones.iter() {
+                c.fetch_stat().await.unwrap();
+            }
+        }
+
+        web_stat_mock.assert_hits(6);
+        assert!(start.elapsed() >= std::time::Duration::from_millis(450));
     }
 
     #[tokio::test]
@@ -477,10 +1117,237 @@ mod tests {
 
         assert!(res.is_err());
         if let Err(e) = res {
-            assert!(e.starts_with("failed to deserialize response body: "));
+            assert!(matches!(e, Error::Deserialize(_)));
+            assert!(e
+                .to_string()
+                .starts_with("failed to deserialize response body: "));
         }
     }
 
+    #[tokio::test]
+    async fn fetch_range_invalid_created_skipped() {
+        let server = httpmock::MockServer::start();
+        let web_range_mock = server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/v0/web/range")
+                .query_param("min_wid", "100")
+                .query_param("max_wid", "200");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .body(r#"{"entries":[
+                    {"id":100,"created":"not a date","url":"https://example.com/s/1/1","status":200,"response":"AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"},
+                    {"id":101,"created":"2023-06-02T23:24:25.065Z","url":"https://example.com/s/1/2","status":200,"response":"AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"}
+                ]}"#);
+        });
+
+        let base_url = Url::parse(&server.base_url()).unwrap();
+        let client = reqwest::Client::new();
+        let client = super::Client::new(client, base_url, USER, PASS);
+
+        let webs = client.fetch_range(100, 200, None).await.unwrap();
+        assert_eq!(webs.iter().map(|w| w.id).collect::<Vec<_>>(), [101]);
+
+        let report = client.fetch_range_report(100, 200, None).await.unwrap();
+        assert_eq!(report.webs.len(), 1);
+        assert_eq!(report.invalid.len(), 1);
+        assert_eq!(report.invalid[0].id, Some(100));
+
+        // Streams yield the invalid entry as an error and carry on.
+        let res = client
+            .stream_range(100, 200, None)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(res.len(), 2);
+        assert!(matches!(
+            res[0],
+            Err(Error::InvalidEntry(InvalidEntry { id: Some(100), .. }))
+        ));
+        assert_eq!(res[1].as_ref().unwrap().id, 101);
+
+        let res = client
+            .fetch_range_stream(100, 200, None)
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+        assert!(matches!(res[0], Err(Error::InvalidEntry(_))));
+        assert_eq!(res[1].as_ref().unwrap().id, 101);
+
+        web_range_mock.assert_hits(4);
+    }
+
+    #[tokio::test]
+    async fn stream_range_success() {
+        let server = httpmock::MockServer::start();
+        let first_mock = server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/v0/web/range")
+                .query_param("min_wid", "100")
+                .query_param("max_wid", "1100")
+                .query_param("url_like", "%foo%");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .body(r#"{"entries":[
+                    {"id":100,"created":"2023-06-01T23:24:25.065Z","url":"https://example.com/s/1/1","status":200,"response":"AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"},
+                    {"id":1099,"created":"2023-06-02T23:24:25.065Z","url":"https://example.com/s/1/2","status":200,"response":"AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"}
+                ]}"#);
+        });
+        let second_mock = server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/v0/web/range")
+                .query_param("min_wid", "1100")
+                .query_param("max_wid", "1500")
+                .query_param("url_like", "%foo%");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .body(r#"{"entries":[
+                    {"id":1400,"created":"2023-06-03T23:24:25.065Z","url":"https://example.com/s/2/1","status":200,"response":"AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"}
+                ]}"#);
+        });
+
+        let base_url = Url::parse(&server.base_url()).unwrap();
+        let client = reqwest::Client::new();
+        let client = super::Client::new(client, base_url, USER, PASS);
+
+        let res = client
+            .stream_range(100, 1500, Some("%foo%"))
+            .try_collect::<Vec<_>>()
+            .await;
+
+        first_mock.assert();
+        second_mock.assert();
+
+        let ids = res.unwrap().iter().map(|w| w.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![100, 1099, 1400]);
+    }
+
+    #[tokio::test]
+    async fn stream_range_stops_after_error() {
+        let server = httpmock::MockServer::start();
+        let web_range_mock = server.mock(|when, then| {
+            when.method(httpmock::Method::GET).path("/v0/web/range");
+            then.status(500)
+                .header("Content-Type", "application/json")
+                .body(r#"{"err":1}"#);
+        });
+
+        let base_url = Url::parse(&server.base_url()).unwrap();
+        let client = reqwest::Client::new();
+        let client = super::Client::new(client, base_url, USER, PASS);
+
+        let res = client
+            .stream_range(100, 5000, None)
+            .collect::<Vec<_>>()
+            .await;
+
+        web_range_mock.assert_hits(1);
+        assert_eq!(res.len(), 1);
+        assert!(res[0].is_err());
+    }
+
+    #[tokio::test]
+    async fn stream_range_parallel_ordered() {
+        let server = httpmock::MockServer::start();
+        let mocks = [(0, 1000, 300), (1000, 2000, 0), (2000, 2500, 100)].map(|(lo, hi, delay)| {
+            server.mock(|when, then| {
+                when.method(httpmock::Method::GET)
+                    .path("/v0/web/range")
+                    .query_param("min_wid", lo.to_string())
+                    .query_param("max_wid", hi.to_string());
+                then.status(200)
+                    .header("Content-Type", "application/json")
+                    .delay(std::time::Duration::from_millis(delay))
+                    .body(
+                        r#"{"entries":[
+                            {"id":ID_B,"created":"2023-06-01T23:24:25.065Z","url":"https://example.com/s/1/1","status":200,"response":"AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"},
+                            {"id":ID_A,"created":"2023-06-01T23:24:25.065Z","url":"https://example.com/s/1/1","status":200,"response":"AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"}
+                        ]}"#
+                        .replace("ID_A", &lo.to_string())
+                        .replace("ID_B", &(lo + 1).to_string()),
+                    );
+            })
+        });
+
+        let base_url = Url::parse(&server.base_url()).unwrap();
+        let client = reqwest::Client::new();
+        let client = super::Client::new(client, base_url, USER, PASS);
+
+        let res = client
+            .stream_range_parallel(0, 2500, None, 3)
+            .try_collect::<Vec<_>>()
+            .await;
+
+        mocks.iter().for_each(|m| m.assert());
+
+        let ids = res.unwrap().iter().map(|w| w.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![0, 1, 1000, 1001, 2000, 2001]);
+    }
+
+    #[tokio::test]
+    async fn fetch_range_stream_success() {
+        let server = httpmock::MockServer::start();
+        let web_range_mock = server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/v0/web/range")
+                .header("Authorization", basic_auth(USER, PASS))
+                .header("User-Agent", user_agent(USER))
+                .query_param("min_wid", "100")
+                .query_param("max_wid", "1100");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .body(r#"{"entries":[
+                    {"id":100,"created":"2023-06-01T23:24:25.065Z","url":"https://example.com/s/1/1","status":200,"response":"AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"},
+                    {"id":101,"created":"2023-06-02T23:24:25.065Z","url":"https://example.com/s/1/2","status":200,"response":"AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"}
+                ]}"#);
+        });
+
+        let base_url = Url::parse(&server.base_url()).unwrap();
+        let client = reqwest::Client::new();
+        let client = super::Client::new(client, base_url, USER, PASS);
+
+        let res = client
+            .fetch_range_stream(100, 1100, None)
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await;
+
+        web_range_mock.assert();
+
+        let ids = res.unwrap().iter().map(|w| w.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![100, 101]);
+    }
+
+    #[tokio::test]
+    async fn fetch_range_stream_error_truncated() {
+        let server = httpmock::MockServer::start();
+        let web_range_mock = server.mock(|when, then| {
+            when.method(httpmock::Method::GET).path("/v0/web/range");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .body(r#"{"entries":[
+                    {"id":100,"created":"2023-06-01T23:24:25.065Z","url":"https://example.com/s/1/1","status":200,"response":"AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"},
+                    {"id":101,"#);
+        });
+
+        let base_url = Url::parse(&server.base_url()).unwrap();
+        let client = reqwest::Client::new();
+        let client = super::Client::new(client, base_url, USER, PASS);
+
+        let res = client
+            .fetch_range_stream(100, 200, None)
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+
+        web_range_mock.assert();
+
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].as_ref().unwrap().id, 100);
+        assert!(matches!(res[1], Err(Error::Deserialize(_))));
+    }
+
     async fn decompress_test(name: &str, needle: &str, response: &str) {
         let server = httpmock::MockServer::start();
         let web_range_mock = server.mock(|when, then| {
@@ -507,7 +1374,11 @@ mod tests {
 
         assert!(res.is_err(), "decompress_test({name})");
         if let Err(e) = res {
-            assert_eq!(e, needle, "decompress_test({name})");
+            assert!(
+                matches!(e, Error::DecompressAll(_)),
+                "decompress_test({name})"
+            );
+            assert_eq!(e.to_string(), needle, "decompress_test({name})");
         }
     }
 
@@ -522,11 +1393,18 @@ mod tests {
 
         decompress_test(
             "bad_data",
-            "failed to decompress response: decompression error: could not read to end: deflate decompression error",
+            "failed to decompress response: decompression error: could not read to end: corrupt deflate stream",
             "AAAADHicS61IzC3ISVVIyk+pBAAfFwS6",
         )
         .await;
 
+        decompress_test(
+            "exceeds_header",
+            "failed to decompress response: decompression error: output exceeds expected 5 bytes",
+            "AAAABXicS61IzC3ISVVIyk+pBAAfFwS7",
+        )
+        .await;
+
         decompress_test(
             "mismatched_len",
             "failed to decompress response: decompression error: expected 100 bytes, got 12",
@@ -535,6 +1413,157 @@ mod tests {
         .await;
     }
 
+    #[tokio::test]
+    async fn fetch_range_report_partial_failure() {
+        let server = httpmock::MockServer::start();
+        let web_range_mock = server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/v0/web/range")
+                .query_param("min_wid", "100")
+                .query_param("max_wid", "200");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .body(r#"{"entries":[
+                    {"id":100,"created":"2023-06-01T23:24:25.065Z","url":"https://example.com/s/1/1","status":200,"response":"AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"},
+                    {"id":101,"created":"2023-06-02T23:24:25.065Z","url":"https://example.com/s/1/2","status":200,"response":"XDA="},
+                    {"id":102,"created":"2023-06-03T23:24:25.065Z","url":"https://example.com/s/2/1","status":200,"response":"AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"}
+                ]}"#);
+        });
+
+        let base_url = Url::parse(&server.base_url()).unwrap();
+        let client = reqwest::Client::new();
+        let client = super::Client::new(client, base_url, USER, PASS);
+
+        let res = client.fetch_range_report(100, 200, None).await;
+
+        web_range_mock.assert();
+
+        let res = res.unwrap();
+        let ids = res.webs.iter().map(|w| w.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![100, 102]);
+        assert_eq!(res.failures.len(), 1);
+        assert_eq!(res.failures[0].id, 101);
+        assert_eq!(res.failures[0].url, "https://example.com/s/1/2");
+        assert!(matches!(
+            res.failures[0].error,
+            DecompressError::MissingHeader { len: 2 }
+        ));
+    }
+
+    #[tokio::test]
+    async fn client_owned_credentials() {
+        fn assert_static_send_sync<T: Send + Sync + 'static>(_: &T) {}
+
+        let server = httpmock::MockServer::start();
+        let web_stat_mock = server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/v0/web/stat")
+                .header("Authorization", basic_auth(USER, PASS))
+                .header("User-Agent", user_agent(USER));
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .body(r#"{"max_wid":1024}"#);
+        });
+
+        let (user, pass) = (USER.to_string(), PASS.to_string());
+        let base_url = Url::parse(&server.base_url()).unwrap();
+        let client = super::Client::new(reqwest::Client::new(), base_url, user, pass);
+        assert_static_send_sync(&client);
+
+        let res = tokio::spawn(async move { client.fetch_stat().await })
+            .await
+            .unwrap();
+
+        web_stat_mock.assert();
+        assert_eq!(res.unwrap().max_wid, 1024);
+    }
+
+    #[tokio::test]
+    async fn fetch_stat_builder() {
+        let server = httpmock::MockServer::start();
+        let web_stat_mock = server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/v0/web/stat")
+                .header("Authorization", basic_auth(USER, PASS))
+                .header("User-Agent", format!("{} replicator/2", user_agent(USER)))
+                .header("X-Team", "archive");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .delay(std::time::Duration::from_millis(500))
+                .body(r#"{"max_wid":1024}"#);
+        });
+
+        let client = super::Client::builder(USER, PASS)
+            .base_url(Url::parse(&server.base_url()).unwrap())
+            .user_agent_suffix("replicator/2")
+            .default_header(
+                reqwest::header::HeaderName::from_static("x-team"),
+                reqwest::header::HeaderValue::from_static("archive"),
+            )
+            .connect_timeout(Duration::from_secs(5))
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+
+        let res = client.fetch_stat().await;
+
+        web_stat_mock.assert();
+        assert_eq!(res.unwrap().max_wid, 1024);
+
+        let client = super::Client {
+            timeout: Some(Duration::from_millis(50)),
+            ..client
+        };
+        let res = client.fetch_stat().await;
+        assert!(matches!(res, Err(Error::Transport(_))));
+    }
+
+    #[tokio::test]
+    async fn fetch_stat_rotated_credentials() {
+        #[derive(Debug, Default)]
+        struct Rotating(std::sync::atomic::AtomicUsize);
+
+        impl CredentialProvider for Rotating {
+            fn credentials(&self) -> Result<Credentials, Error> {
+                let n = self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(Credentials::new(USER, format!("{PASS}_{n}")))
+            }
+        }
+
+        let server = httpmock::MockServer::start();
+        let mocks = [0, 1].map(|n| {
+            server.mock(|when, then| {
+                when.method(httpmock::Method::GET)
+                    .path("/v0/web/stat")
+                    .header("Authorization", basic_auth(USER, &format!("{PASS}_{n}")))
+                    .header("User-Agent", user_agent(USER));
+                then.status(200)
+                    .header("Content-Type", "application/json")
+                    .body(r#"{"max_wid":1024}"#);
+            })
+        });
+
+        let client = super::ClientBuilder::from_credentials(Rotating::default())
+            .base_url(Url::parse(&server.base_url()).unwrap())
+            .build()
+            .unwrap();
+
+        client.fetch_stat().await.unwrap();
+        client.fetch_stat().await.unwrap();
+
+        mocks.iter().for_each(|m| m.assert());
+    }
+
+    #[test]
+    fn client_debug_redacted() {
+        let client = super::Client::builder(USER, PASS).build().unwrap();
+
+        let debug = format!("{client:?}");
+        assert!(debug.contains(USER), "{debug}");
+        assert!(!debug.contains(PASS), "{debug}");
+        assert!(debug.contains("[redacted]"), "{debug}");
+    }
+
     #[tokio::test]
     async fn fetch_stat_success() {
         let server = httpmock::MockServer::start();
@@ -560,4 +1589,63 @@ mod tests {
         let res = res.unwrap();
         assert_eq!(res.max_wid, 1024);
     }
+
+    // FakeTransport records each request and replies with the next canned response.
+    #[derive(Debug, Default)]
+    struct FakeTransport {
+        requests: std::sync::Mutex<Vec<Request>>,
+        responses: std::sync::Mutex<std::collections::VecDeque<(u16, &'static str)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Transport for FakeTransport {
+        async fn send(&self, req: Request) -> Result<Response, Error> {
+            self.requests.lock().unwrap().push(req);
+            let (status, body) = self
+                .responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or_else(|| Error::Transport("no canned response".into()))?;
+            Ok(Response {
+                status: http::StatusCode::from_u16(status).unwrap(),
+                headers: HeaderMap::new(),
+                body: Body::from_bytes(body),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_range_fake_transport() {
+        let transport = Arc::new(FakeTransport::default());
+        transport.responses.lock().unwrap().extend([
+            (503, r#"{"err":503}"#),
+            (
+                200,
+                r#"{"entries":[{"id":100,"created":"2023-06-01T23:24:25.065Z","url":"https://example.com/s/1/1","status":200,"response":"AAAADHicS61IzC3ISVVIyk+pBAAfFwS7"}]}"#,
+            ),
+        ]);
+
+        let client = super::Client::builder(USER, PASS)
+            .transport(transport.clone())
+            .retry_policy(RetryPolicy {
+                base_delay: Duration::ZERO,
+                ..RetryPolicy::default()
+            })
+            .build()
+            .unwrap();
+
+        let res = client.fetch_range(100, 200, Some("%foo%")).await.unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].response, b"example body");
+
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[0].url.as_str(),
+            format!("{DEFAULT_BASE_URL}v0/web/range?min_wid=100&max_wid=200&url_like=%25foo%25")
+        );
+        assert_eq!(requests[1].headers["Authorization"], basic_auth(USER, PASS));
+        assert_eq!(requests[1].headers["User-Agent"], user_agent(USER).as_str());
+    }
 }
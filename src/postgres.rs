@@ -0,0 +1,229 @@
+use crate::storage::{from_row, storage_error};
+use crate::{CompressedWeb, Error, Storage};
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use time::OffsetDateTime;
+
+// SCHEMA creates the `web` table, see `sql/postgres/001_init.sql`.
+const SCHEMA: &str = include_str!("../sql/postgres/001_init.sql");
+
+// PG_EPOCH is the origin of postgres' binary timestamp encoding.
+const PG_EPOCH: OffsetDateTime = time::macros::datetime!(2000-01-01 0:00 UTC);
+
+// PostgresStorage is a `Storage` backed by the `web` table of a PostgreSQL database, storing
+// `created` as `timestamptz` and `response` as `bytea`.
+//
+// Batches are bulk loaded with `COPY` into a temporary table and then upserted.
+#[derive(Clone, Debug)]
+pub struct PostgresStorage {
+    pub pool: PgPool,
+}
+
+impl PostgresStorage {
+    // connect connects to `url`, creating the `web` table if needed.
+    pub async fn connect(url: &str) -> Result<Self, Error> {
+        let pool = PgPoolOptions::new()
+            .connect(url)
+            .await
+            .map_err(storage_error)?;
+        Self::from_pool(pool).await
+    }
+
+    // from_pool uses an existing pool, creating the `web` table if needed.
+    pub async fn from_pool(pool: PgPool) -> Result<Self, Error> {
+        sqlx::query(SCHEMA)
+            .execute(&pool)
+            .await
+            .map_err(storage_error)?;
+        Ok(Self { pool })
+    }
+}
+
+// copy_binary encodes `webs` in the binary `COPY` format for the columns
+// `(id, created, url, status, response)`.
+fn copy_binary(webs: &[CompressedWeb]) -> Result<Vec<u8>, Error> {
+    fn field(buf: &mut Vec<u8>, v: &[u8]) -> Result<(), Error> {
+        let len = i32::try_from(v.len())
+            .map_err(|_| Error::Storage(format!("field too large: {} bytes", v.len()).into()))?;
+        buf.extend_from_slice(&len.to_be_bytes());
+        buf.extend_from_slice(v);
+        Ok(())
+    }
+
+    let mut buf = b"PGCOPY\n\xff\r\n\0".to_vec();
+    buf.extend_from_slice(&0i32.to_be_bytes()); // flags
+    buf.extend_from_slice(&0i32.to_be_bytes()); // header extension length
+    for w in webs {
+        let created = i64::try_from((w.created - PG_EPOCH).whole_microseconds())
+            .map_err(|_| Error::Storage(format!("created out of range: {}", w.created).into()))?;
+
+        buf.extend_from_slice(&5i16.to_be_bytes());
+        field(&mut buf, &w.id.to_be_bytes())?;
+        field(&mut buf, &created.to_be_bytes())?;
+        field(&mut buf, w.url.as_bytes())?;
+        field(&mut buf, &w.status.to_be_bytes())?;
+        field(&mut buf, &w.response)?;
+    }
+    buf.extend_from_slice(&(-1i16).to_be_bytes());
+    Ok(buf)
+}
+
+#[async_trait::async_trait]
+impl Storage for PostgresStorage {
+    async fn insert_batch(&self, webs: &[CompressedWeb]) -> Result<u64, Error> {
+        let data = copy_binary(webs)?;
+
+        let mut tx = self.pool.begin().await.map_err(storage_error)?;
+        // ord numbers the copied rows in batch order, so that the last of several entries with
+        // the same id wins.
+        sqlx::query(
+            "create temporary table web_copy (like web, ord bigint generated always as identity)
+            on commit drop",
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(storage_error)?;
+
+        let mut copy = tx
+            .copy_in_raw(
+                "copy web_copy(id, created, url, status, response) from stdin (format binary)",
+            )
+            .await
+            .map_err(storage_error)?;
+        copy.send(data).await.map_err(storage_error)?;
+        copy.finish().await.map_err(storage_error)?;
+
+        let upserted = sqlx::query(
+            "insert into web(id, created, url, status, response)
+            select distinct on (id) id, created, url, status, response from web_copy
+            order by id, ord desc
+            on conflict (id) do update set
+                created = excluded.created,
+                url = excluded.url,
+                status = excluded.status,
+                response = excluded.response
+            where (web.created, web.url, web.status, web.response)
+                is distinct from (excluded.created, excluded.url, excluded.status, excluded.response)",
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(storage_error)?
+        .rows_affected();
+
+        tx.commit().await.map_err(storage_error)?;
+        Ok(upserted)
+    }
+
+    async fn max_id(&self) -> Result<Option<i64>, Error> {
+        sqlx::query_scalar("select max(id) from web")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(storage_error)
+    }
+
+    async fn get(&self, id: i64) -> Result<Option<CompressedWeb>, Error> {
+        sqlx::query("select id, created, url, status, response from web where id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(storage_error)?
+            .map(|row| from_row(&row))
+            .transpose()
+    }
+
+    fn range(&self, min_wid: i64, max_wid: i64) -> BoxStream<'_, Result<CompressedWeb, Error>> {
+        sqlx::query(
+            "select id, created, url, status, response from web where id >= $1 and id < $2 order by id",
+        )
+        .bind(min_wid)
+        .bind(max_wid)
+        .fetch(&self.pool)
+        .map_err(storage_error)
+        .and_then(|row| async move { from_row(&row) })
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::compressed;
+
+    // TEST_URL_VAR names the database used by the tests below. They are skipped when it is
+    // unset, and drop the `web` table of that database.
+    const TEST_URL_VAR: &str = "SKITTER_RO_TEST_POSTGRES_URL";
+
+    #[test]
+    fn copy_binary_encoding() {
+        let web = CompressedWeb {
+            response: vec![0, 0, 0, 0, 0x78, 0x9c],
+            ..compressed(1, "body")
+        };
+        let buf = copy_binary(&[web]).unwrap();
+
+        let mut expected = b"PGCOPY\n\xff\r\n\0\0\0\0\0\0\0\0\0".to_vec();
+        expected.extend_from_slice(&[0, 5]);
+        expected.extend_from_slice(&[0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 1]);
+        // 2023-06-03 20:59:52.632 UTC is 739141192632000 microseconds after 2000-01-01.
+        expected.extend_from_slice(&[0, 0, 0, 8]);
+        expected.extend_from_slice(&739141192632000i64.to_be_bytes());
+        expected.extend_from_slice(&[0, 0, 0, 25]);
+        expected.extend_from_slice(b"https://example.com/s/1/1");
+        expected.extend_from_slice(&[0, 0, 0, 2, 0, 200]);
+        expected.extend_from_slice(&[0, 0, 0, 6, 0, 0, 0, 0, 0x78, 0x9c]);
+        expected.extend_from_slice(&[0xff, 0xff]);
+
+        assert_eq!(buf, expected);
+    }
+
+    #[tokio::test]
+    async fn insert_and_query() {
+        let Ok(url) = std::env::var(TEST_URL_VAR) else {
+            return;
+        };
+        let pool = PgPoolOptions::new().connect(&url).await.unwrap();
+        sqlx::query("drop table if exists web")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let s = PostgresStorage::from_pool(pool).await.unwrap();
+
+        assert_eq!(s.max_id().await.unwrap(), None);
+        assert_eq!(
+            s.insert_batch(&[compressed(100, "body"), compressed(102, "body")])
+                .await
+                .unwrap(),
+            2
+        );
+
+        // Re-inserting an unchanged entry is a no-op, a changed one replaces the stored entry.
+        let mut changed = compressed(102, "body");
+        changed.url = "https://example.com/changed".to_string();
+        let mut overwritten = compressed(102, "body");
+        overwritten.url = "https://example.com/overwritten".to_string();
+        let batch = [
+            compressed(100, "body"),
+            compressed(101, "body"),
+            overwritten,
+            changed,
+        ];
+        assert_eq!(s.insert_batch(&batch).await.unwrap(), 2);
+        assert_eq!(s.insert_batch(&batch).await.unwrap(), 0);
+
+        assert_eq!(s.max_id().await.unwrap(), Some(102));
+        assert_eq!(s.get(100).await.unwrap(), Some(compressed(100, "body")));
+        assert_eq!(
+            s.get(102).await.unwrap().unwrap().url,
+            "https://example.com/changed"
+        );
+        assert_eq!(s.get(103).await.unwrap(), None);
+
+        let ids = s
+            .range(101, 103)
+            .map_ok(|w| w.id)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(ids, [101, 102]);
+    }
+}
@@ -0,0 +1,192 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+// RateLimit configures the client side token buckets applied to every request.
+//
+// Each limit allows bursts of up to one second worth of tokens, and of at least one token.
+// Response bytes are only known once a body has been read, so the byte bucket may go into debt,
+// delaying the next request until it has been paid back.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimit {
+    pub requests_per_sec: Option<f64>,
+    pub bytes_per_sec: Option<f64>,
+}
+
+impl RateLimit {
+    pub fn requests_per_sec(requests_per_sec: f64) -> Self {
+        Self {
+            requests_per_sec: Some(requests_per_sec),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_bytes_per_sec(mut self, bytes_per_sec: f64) -> Self {
+        self.bytes_per_sec = Some(bytes_per_sec);
+        self
+    }
+}
+
+// RateLimiter is a token bucket limiter shared by every clone of the `Client` it belongs to.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    requests: Option<Bucket>,
+    bytes: Option<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    rate: f64,
+    // capacity is one second worth of tokens, but at least one so that rates below one
+    // request per second can still fill the bucket up to a whole request.
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(rate: f64, now: Instant) -> Self {
+        let capacity = rate.max(1.0);
+        Self {
+            rate,
+            capacity,
+            tokens: capacity,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+    }
+
+    // wait returns how long until the bucket holds at least `n` tokens.
+    fn wait(&self, n: f64) -> Duration {
+        if self.tokens >= n {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((n - self.tokens) / self.rate)
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        let now = Instant::now();
+        Self {
+            state: Arc::new(Mutex::new(State {
+                requests: limit
+                    .requests_per_sec
+                    .filter(|r| *r > 0.0)
+                    .map(|r| Bucket::new(r, now)),
+                bytes: limit
+                    .bytes_per_sec
+                    .filter(|r| *r > 0.0)
+                    .map(|r| Bucket::new(r, now)),
+            })),
+        }
+    }
+
+    // acquire waits until a request may be sent, and takes one request token.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+                let mut wait = Duration::ZERO;
+                if let Some(b) = state.bytes.as_mut() {
+                    b.refill(now);
+                    wait = wait.max(b.wait(0.0));
+                }
+                if let Some(b) = state.requests.as_mut() {
+                    b.refill(now);
+                    wait = wait.max(b.wait(1.0));
+                }
+                if wait.is_zero() {
+                    if let Some(b) = state.requests.as_mut() {
+                        b.tokens -= 1.0;
+                    }
+                    return;
+                }
+                wait
+            };
+            tracing::debug!(?wait, "rate limited");
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    // consume_bytes charges `n` received bytes against the byte bucket.
+    pub fn consume_bytes(&self, n: usize) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(b) = state.bytes.as_mut() {
+            b.refill(Instant::now());
+            b.tokens -= n as f64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_requests_per_sec() {
+        let limiter = RateLimiter::new(RateLimit::requests_per_sec(2.0));
+        let start = Instant::now();
+
+        // The initial burst is immediate, then one request every 500ms.
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        limiter.clone().acquire().await;
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::from_millis(1000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_below_one_per_sec() {
+        let limiter = RateLimiter::new(RateLimit::requests_per_sec(0.5));
+        let start = Instant::now();
+
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::from_secs(4));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_bytes_per_sec() {
+        let limiter = RateLimiter::new(RateLimit::default().with_bytes_per_sec(1000.0));
+        let start = Instant::now();
+
+        limiter.acquire().await;
+        limiter.consume_bytes(3000);
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        // 2000 bytes of debt take two seconds to pay back.
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_unlimited() {
+        let limiter = RateLimiter::new(RateLimit::default());
+        let start = Instant::now();
+
+        for _ in 0..100 {
+            limiter.acquire().await;
+            limiter.consume_bytes(1 << 20);
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,361 @@
+use crate::{CompressedWeb, Error, InvalidEntry, SkitterApi, MAX_RANGE};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+// CheckpointStore persists a `Replicator`'s cursor: for each `url_like` filter, the first wid
+// that has not been fully scanned yet. The filter `None` is stored under the empty string.
+pub trait CheckpointStore: fmt::Debug + Send + Sync {
+    fn load(&self, filter: &str) -> Result<Option<i64>, Error>;
+    fn save(&self, filter: &str, next_wid: i64) -> Result<(), Error>;
+}
+
+// MemoryCheckpointStore keeps checkpoints for the lifetime of the process.
+#[derive(Debug, Default)]
+pub struct MemoryCheckpointStore(Mutex<HashMap<String, i64>>);
+
+impl CheckpointStore for MemoryCheckpointStore {
+    fn load(&self, filter: &str) -> Result<Option<i64>, Error> {
+        Ok(self.0.lock().unwrap().get(filter).copied())
+    }
+
+    fn save(&self, filter: &str, next_wid: i64) -> Result<(), Error> {
+        self.0.lock().unwrap().insert(filter.to_string(), next_wid);
+        Ok(())
+    }
+}
+
+// FileCheckpointStore keeps checkpoints in a JSON object mapping filters to wids. Each save
+// writes and syncs a uniquely named temporary file before renaming it over the checkpoint file,
+// so a crash never leaves it truncated.
+//
+// Clones share a lock, so replicators using clones of one store never overwrite each other's
+// checkpoints. Stores created separately for the same file, including ones in other processes,
+// must not save concurrently.
+#[derive(Clone, Debug)]
+pub struct FileCheckpointStore {
+    pub path: PathBuf,
+    lock: Arc<Mutex<()>>,
+}
+
+impl FileCheckpointStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Arc::default(),
+        }
+    }
+
+    // tmp_path returns a path next to `path` that no other save uses.
+    fn tmp_path(&self) -> PathBuf {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(format!(
+            ".{}-{}.tmp",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        tmp.into()
+    }
+
+    fn read(&self) -> Result<HashMap<String, i64>, Error> {
+        match std::fs::read(&self.path) {
+            Ok(contents) => serde_json::from_slice(&contents).map_err(|e| self.error(e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(self.error(e)),
+        }
+    }
+
+    fn error(&self, e: impl fmt::Display) -> Error {
+        Error::Checkpoint(format!("{}: {e}", self.path.display()).into())
+    }
+}
+
+impl CheckpointStore for FileCheckpointStore {
+    fn load(&self, filter: &str) -> Result<Option<i64>, Error> {
+        Ok(self.read()?.get(filter).copied())
+    }
+
+    fn save(&self, filter: &str, next_wid: i64) -> Result<(), Error> {
+        let _guard = self.lock.lock().unwrap();
+        let mut checkpoints = self.read()?;
+        checkpoints.insert(filter.to_string(), next_wid);
+
+        let tmp = self.tmp_path();
+        let contents = serde_json::to_vec_pretty(&checkpoints).map_err(|e| self.error(e))?;
+        std::fs::File::create(&tmp)
+            .and_then(|mut f| {
+                f.write_all(&contents)?;
+                f.sync_all()
+            })
+            .and_then(|()| std::fs::rename(&tmp, &self.path))
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&tmp);
+                self.error(e)
+            })
+    }
+}
+
+// Block is a scanned half-open range `[min_wid, max_wid)` and the entries matching the
+// replicator's filter. It may be empty.
+#[derive(Debug)]
+pub struct Block {
+    pub min_wid: i64,
+    pub max_wid: i64,
+    pub entries: Vec<CompressedWeb>,
+    // invalid lists the entries of the range that could not be deserialized. They are not
+    // fetched again once the block is committed, so handle them before calling `commit`.
+    pub invalid: Vec<InvalidEntry>,
+}
+
+// Replicator walks the API in `MAX_RANGE` sized blocks, persisting its progress in a
+// `CheckpointStore` so that a restart resumes after the last committed block.
+//
+// Progress is tracked by the ranges scanned rather than the ids stored, so a sparse `url_like`
+// filter never causes the same range to be queried twice. Call `commit` once a block's entries
+// have been stored; an uncommitted block is fetched again by the next `next_block`.
+#[derive(Debug)]
+pub struct Replicator<A, S> {
+    api: A,
+    store: S,
+    url_like: Option<String>,
+    start_wid: i64,
+    // next_wid is the cursor, loaded from `store` on first use.
+    next_wid: Option<i64>,
+    // max_wid is the last `fetch_stat` result.
+    max_wid: Option<i64>,
+}
+
+impl<A: SkitterApi, S: CheckpointStore> Replicator<A, S> {
+    // new starts from `start_wid` unless `store` has a checkpoint for `url_like`.
+    pub fn new(api: A, store: S, url_like: Option<String>, start_wid: i64) -> Self {
+        Self {
+            api,
+            store,
+            url_like,
+            start_wid,
+            next_wid: None,
+            max_wid: None,
+        }
+    }
+
+    fn filter(&self) -> &str {
+        self.url_like.as_deref().unwrap_or("")
+    }
+
+    // next_wid is the first wid that has not been committed yet.
+    pub fn next_wid(&mut self) -> Result<i64, Error> {
+        if let Some(next_wid) = self.next_wid {
+            return Ok(next_wid);
+        }
+        let next_wid = self.store.load(self.filter())?.unwrap_or(self.start_wid);
+        self.next_wid = Some(next_wid);
+        Ok(next_wid)
+    }
+
+    // next_block fetches the block starting at `next_wid`, or returns `None` once the cursor has
+    // passed the server's `max_wid`. The final block may be shorter than `MAX_RANGE`; once it
+    // is committed, later calls only scan ids that have appeared since.
+    pub async fn next_block(&mut self) -> Result<Option<Block>, Error> {
+        let min_wid = self.next_wid()?;
+        if self.max_wid.is_none_or(|max_wid| min_wid > max_wid) {
+            self.max_wid = Some(self.api.fetch_stat().await?.max_wid);
+        }
+        let max_wid = match self.max_wid {
+            Some(max_wid) if min_wid <= max_wid => (min_wid + MAX_RANGE).min(max_wid + 1),
+            _ => return Ok(None),
+        };
+
+        let mut block = Block {
+            min_wid,
+            max_wid,
+            entries: vec![],
+            invalid: vec![],
+        };
+        for e in self
+            .api
+            .fetch_range_entries(min_wid, max_wid, self.url_like.as_deref())
+            .await?
+        {
+            match e {
+                Ok(w) => block.entries.push(w),
+                Err(e) => block.invalid.push(e),
+            }
+        }
+        Ok(Some(block))
+    }
+
+    // commit marks `block` as scanned, advancing the persisted cursor to its `max_wid`.
+    pub fn commit(&mut self, block: &Block) -> Result<(), Error> {
+        let next_wid = self.next_wid()?;
+        if block.min_wid != next_wid {
+            return Err(Error::Checkpoint(
+                format!(
+                    "block [{}, {}) does not start at the cursor {next_wid}",
+                    block.min_wid, block.max_wid
+                )
+                .into(),
+            ));
+        }
+        self.store.save(self.filter(), block.max_wid)?;
+        self.next_wid = Some(block.max_wid);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_path, web_at};
+    use crate::MemoryApi;
+
+    #[tokio::test]
+    async fn replicate_sparse_filter() {
+        let api = MemoryApi::new(vec![
+            web_at(100, "https://example.com/s/1/1"),
+            web_at(1500, "https://example.org/s/1/1"),
+            web_at(2600, "https://example.com/s/2/1"),
+        ]);
+        let mut r = Replicator::new(
+            api,
+            MemoryCheckpointStore::default(),
+            Some("https://example.com/%".to_string()),
+            0,
+        );
+
+        let mut blocks = vec![];
+        while let Some(block) = r.next_block().await.unwrap() {
+            r.commit(&block).unwrap();
+            let ids = block.entries.iter().map(|w| w.id).collect::<Vec<_>>();
+            blocks.push((block.min_wid, block.max_wid, ids));
+        }
+
+        assert_eq!(
+            blocks,
+            [
+                (0, 1000, vec![100]),
+                (1000, 2000, vec![]),
+                (2000, 2601, vec![2600]),
+            ]
+        );
+        assert_eq!(r.next_wid().unwrap(), 2601);
+        assert_eq!(r.store.load("https://example.com/%").unwrap(), Some(2601));
+    }
+
+    #[tokio::test]
+    async fn replicate_resume_and_uncommitted() {
+        let store = MemoryCheckpointStore::default();
+        store.save("", 1000).unwrap();
+        let api = MemoryApi::new(vec![web_at(500, "a"), web_at(1200, "b")]);
+        let mut r = Replicator::new(api, store, None, 0);
+
+        let block = r.next_block().await.unwrap().unwrap();
+        assert_eq!((block.min_wid, block.max_wid), (1000, 1201));
+
+        // Without a commit the same block is returned again.
+        let again = r.next_block().await.unwrap().unwrap();
+        assert_eq!(again.min_wid, 1000);
+
+        r.commit(&block).unwrap();
+        assert!(r.next_block().await.unwrap().is_none());
+        assert!(matches!(r.commit(&block), Err(Error::Checkpoint(_))));
+    }
+
+    // WithInvalid serves the entries of a `MemoryApi` plus an invalid entry 150.
+    struct WithInvalid(MemoryApi);
+
+    #[async_trait::async_trait]
+    impl SkitterApi for WithInvalid {
+        async fn fetch_stat(&self) -> Result<crate::WebStat, Error> {
+            self.0.fetch_stat().await
+        }
+
+        async fn fetch_range_compressed(
+            &self,
+            _: i64,
+            _: i64,
+            _: Option<&str>,
+        ) -> Result<Vec<CompressedWeb>, Error> {
+            unimplemented!()
+        }
+
+        async fn fetch_range_entries(
+            &self,
+            min_wid: i64,
+            max_wid: i64,
+            url_like: Option<&str>,
+        ) -> Result<Vec<Result<CompressedWeb, InvalidEntry>>, Error> {
+            let mut entries = self
+                .0
+                .fetch_range_entries(min_wid, max_wid, url_like)
+                .await?;
+            if (min_wid..max_wid).contains(&150) {
+                entries.push(crate::proto::parse_entry(br#"{"id":150,"url":"c"}"#));
+            }
+            Ok(entries)
+        }
+
+        async fn fetch_range(
+            &self,
+            _: i64,
+            _: i64,
+            _: Option<&str>,
+        ) -> Result<Vec<crate::Web>, Error> {
+            unimplemented!()
+        }
+    }
+
+    #[tokio::test]
+    async fn replicate_invalid_entry() {
+        let api = WithInvalid(MemoryApi::new(vec![web_at(100, "a"), web_at(200, "b")]));
+        let mut r = Replicator::new(api, MemoryCheckpointStore::default(), None, 0);
+
+        let block = r.next_block().await.unwrap().unwrap();
+        let ids = block.entries.iter().map(|w| w.id).collect::<Vec<_>>();
+        assert_eq!(ids, [100, 200]);
+        assert_eq!(block.invalid.len(), 1);
+        assert_eq!(block.invalid[0].id, Some(150));
+        assert_eq!(block.invalid[0].url.as_deref(), Some("c"));
+
+        // The invalid entry is still fetched again until the block is committed.
+        let again = r.next_block().await.unwrap().unwrap();
+        assert_eq!(again.invalid.len(), 1);
+        r.commit(&again).unwrap();
+        assert_eq!(r.next_wid().unwrap(), 201);
+    }
+
+    #[test]
+    fn file_checkpoint_store() {
+        let path = temp_path("checkpoints.json");
+        let store = FileCheckpointStore::new(&path);
+
+        assert_eq!(store.load("%/s/%").unwrap(), None);
+        store.save("%/s/%", 1000).unwrap();
+        store.save("", 2000).unwrap();
+        store.save("%/s/%", 3000).unwrap();
+
+        let store = FileCheckpointStore::new(&path);
+        assert_eq!(store.load("%/s/%").unwrap(), Some(3000));
+        assert_eq!(store.load("").unwrap(), Some(2000));
+
+        // Clones saving different filters concurrently keep each other's checkpoints.
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let store = store.clone();
+                scope.spawn(move || store.save(&i.to_string(), i).unwrap());
+            }
+        });
+        for i in 0..8 {
+            assert_eq!(store.load(&i.to_string()).unwrap(), Some(i));
+        }
+        assert_eq!(store.load("").unwrap(), Some(2000));
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(matches!(store.load(""), Err(Error::Checkpoint(_))));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
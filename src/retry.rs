@@ -0,0 +1,242 @@
+use crate::Error;
+use http::header::HeaderValue;
+use http::StatusCode;
+use std::time::{Duration, SystemTime};
+use time::format_description::well_known::Rfc2822;
+use time::OffsetDateTime;
+
+// RetryPolicy controls how `Client` retries failed requests.
+//
+// Transport failures and responses with one of `retryable_statuses` are retried up to
+// `max_attempts` times in total, sleeping an exponentially increasing delay between attempts.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    // max_attempts is the total number of attempts, including the first one.
+    pub max_attempts: u32,
+    // base_delay is the delay before the second attempt, doubled for each later attempt.
+    pub base_delay: Duration,
+    // max_delay caps both the backoff delay and any server provided `Retry-After`.
+    pub max_delay: Duration,
+    // jitter is the fraction (0.0 to 1.0) of each delay that is randomized.
+    pub jitter: f64,
+    pub retryable_statuses: Vec<StatusCode>,
+    // respect_retry_after uses the `Retry-After` header of 429 and 503 responses as the delay.
+    pub respect_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(60),
+            jitter: 0.2,
+            retryable_statuses: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            respect_retry_after: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    // none makes a single attempt and never retries.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_retryable(&self, err: &Error) -> bool {
+        match err {
+            Error::Transport(_) | Error::Body(_) => true,
+            Error::Status { status, .. } => self.retryable_statuses.contains(status),
+            _ => false,
+        }
+    }
+
+    // delay returns how long to wait after `attempt` (starting at 1) failed with `err`.
+    pub fn delay(&self, attempt: u32, err: &Error) -> Duration {
+        if self.respect_retry_after {
+            if let Error::Status {
+                status: StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE,
+                retry_after: Some(retry_after),
+                ..
+            } = err
+            {
+                return (*retry_after).min(self.max_delay);
+            }
+        }
+
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        backoff.mul_f64(1.0 - jitter * random_unit())
+    }
+
+    // run calls `f` until it succeeds, fails with an error that is not retryable or
+    // `max_attempts` is reached, sleeping `delay` between attempts.
+    #[cfg(feature = "tokio")]
+    pub async fn run<T, F, Fut>(&self, mut f: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T, Error>>,
+    {
+        let mut attempt = 1;
+        loop {
+            tracing::Span::current().record("attempts", attempt);
+            match f().await {
+                Err(e) if attempt < self.max_attempts && self.is_retryable(&e) => {
+                    let delay = self.delay(attempt, &e);
+                    tracing::warn!(attempt, ?delay, error = %e, "request failed, retrying");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    // run_blocking is `run` for `blocking::Client`, sleeping the current thread between attempts.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn run_blocking<T>(
+        &self,
+        mut f: impl FnMut() -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut attempt = 1;
+        loop {
+            tracing::Span::current().record("attempts", attempt);
+            match f() {
+                Err(e) if attempt < self.max_attempts && self.is_retryable(&e) => {
+                    let delay = self.delay(attempt, &e);
+                    tracing::warn!(attempt, ?delay, error = %e, "request failed, retrying");
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+// parse_retry_after accepts both the delay-seconds and HTTP-date forms of `Retry-After`.
+pub(crate) fn parse_retry_after(v: &HeaderValue) -> Option<Duration> {
+    let v = v.to_str().ok()?.trim();
+    if let Ok(secs) = v.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = OffsetDateTime::parse(v, &Rfc2822).ok()?;
+    let now = OffsetDateTime::now_utc();
+    Some((at - now).try_into().unwrap_or(Duration::ZERO))
+}
+
+// random_unit returns a pseudo random value in [0, 1) without pulling in an rng dependency.
+fn random_unit() -> f64 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::BuildHasher;
+
+    let h = RandomState::new().hash_one(SystemTime::now());
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status_err(status: StatusCode, retry_after: Option<Duration>) -> Error {
+        Error::Status {
+            status,
+            api_error: None,
+            body: vec![],
+            retry_after,
+        }
+    }
+
+    #[test]
+    fn delay_exponential_backoff() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+        let err = status_err(StatusCode::BAD_GATEWAY, None);
+
+        assert_eq!(policy.delay(1, &err), Duration::from_millis(100));
+        assert_eq!(policy.delay(2, &err), Duration::from_millis(200));
+        assert_eq!(policy.delay(4, &err), Duration::from_millis(800));
+        assert_eq!(policy.delay(5, &err), Duration::from_millis(1000));
+        assert_eq!(policy.delay(64, &err), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn delay_jitter_bounds() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            jitter: 0.5,
+            ..RetryPolicy::default()
+        };
+        let err = status_err(StatusCode::BAD_GATEWAY, None);
+
+        for _ in 0..100 {
+            let d = policy.delay(1, &err);
+            assert!(d > Duration::from_millis(50) && d <= Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn delay_retry_after() {
+        let policy = RetryPolicy {
+            max_delay: Duration::from_secs(10),
+            ..RetryPolicy::default()
+        };
+
+        let err = status_err(StatusCode::TOO_MANY_REQUESTS, Some(Duration::from_secs(3)));
+        assert_eq!(policy.delay(1, &err), Duration::from_secs(3));
+
+        let err = status_err(
+            StatusCode::SERVICE_UNAVAILABLE,
+            Some(Duration::from_secs(30)),
+        );
+        assert_eq!(policy.delay(1, &err), Duration::from_secs(10));
+
+        let policy = RetryPolicy {
+            respect_retry_after: false,
+            jitter: 0.0,
+            ..policy
+        };
+        assert_eq!(policy.delay(1, &err), policy.base_delay);
+    }
+
+    #[test]
+    fn is_retryable() {
+        let policy = RetryPolicy::default();
+
+        assert!(policy.is_retryable(&status_err(StatusCode::SERVICE_UNAVAILABLE, None)));
+        assert!(!policy.is_retryable(&status_err(StatusCode::BAD_REQUEST, None)));
+        assert!(!policy.is_retryable(&Error::Range {
+            min_wid: 0,
+            max_wid: 2000
+        }));
+    }
+
+    #[test]
+    fn parse_retry_after_values() {
+        assert_eq!(
+            parse_retry_after(&HeaderValue::from_static("120")),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after(&HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT")),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after(&HeaderValue::from_static("soon")), None);
+    }
+}
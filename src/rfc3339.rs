@@ -0,0 +1,99 @@
+// Serde helpers for the RFC3339 `created` timestamps used by the API.
+//
+// `serialize` and `deserialize` are strict and are what `Web` and `CompressedWeb` use. The
+// `lenient` module additionally accepts the space separated variants produced by other tools,
+// and can be used with `#[serde(deserialize_with = "skitter_ro_client::rfc3339::lenient::deserialize")]`.
+
+use serde::{de, ser, Deserialize, Deserializer, Serializer};
+use std::borrow::Cow;
+use time::format_description::well_known::Rfc3339;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::OffsetDateTime;
+
+// DISPLAY_FORMAT matches the `Display` impl of `OffsetDateTime`, e.g.
+// `2023-06-03 20:59:52.632 +00:00:00`.
+const DISPLAY_FORMAT: &[FormatItem<'_>] = format_description!(
+    "[year]-[month]-[day] [hour]:[minute]:[second][optional [.[subsecond]]] [offset_hour sign:mandatory]:[offset_minute]:[offset_second]"
+);
+
+pub fn serialize<S: Serializer>(v: &OffsetDateTime, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&v.format(&Rfc3339).map_err(ser::Error::custom)?)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<OffsetDateTime, D::Error> {
+    // Cow borrows from `&str` input and owns the string when reading from an `io::Read`.
+    let s: Cow<'de, str> = Deserialize::deserialize(d)?;
+    OffsetDateTime::parse(&s, &Rfc3339)
+        .map_err(|e| de::Error::custom(format!("invalid rfc3339 datetime {s:?}: {e}")))
+}
+
+// parse_lenient parses RFC3339 (including a space instead of `T` between the date and time) or
+// the `Display` format of `OffsetDateTime`.
+pub fn parse_lenient(s: &str) -> Result<OffsetDateTime, time::error::Parse> {
+    let err = match OffsetDateTime::parse(s, &Rfc3339) {
+        Ok(v) => return Ok(v),
+        Err(e) => e,
+    };
+
+    OffsetDateTime::parse(s, DISPLAY_FORMAT).map_err(|_| err)
+}
+
+pub mod lenient {
+    use super::*;
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<OffsetDateTime, D::Error> {
+        let s: Cow<'de, str> = Deserialize::deserialize(d)?;
+        parse_lenient(&s).map_err(|e| de::Error::custom(format!("invalid datetime {s:?}: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expected() -> OffsetDateTime {
+        OffsetDateTime::parse("2023-06-03T20:59:52.632Z", &Rfc3339).unwrap()
+    }
+
+    #[test]
+    fn parse_lenient_variants() {
+        for v in [
+            "2023-06-03T20:59:52.632Z",
+            "2023-06-03 20:59:52.632Z",
+            "2023-06-03 20:59:52.632+00:00",
+            "2023-06-03 20:59:52.632 +00:00:00",
+        ] {
+            assert_eq!(parse_lenient(v).ok(), Some(expected()), "{v}");
+        }
+
+        assert_eq!(
+            parse_lenient(&expected().to_string()).ok(),
+            Some(expected())
+        );
+    }
+
+    #[test]
+    fn parse_lenient_invalid() {
+        for v in ["", "2023-06-03", "2023-06-03 20:59:52.632", "yesterday"] {
+            assert!(parse_lenient(v).is_err(), "{v}");
+        }
+    }
+
+    #[test]
+    fn deserialize_strict_rejects_invalid() {
+        #[derive(Deserialize, Debug)]
+        struct T {
+            #[serde(deserialize_with = "super::deserialize")]
+            #[allow(dead_code)]
+            created: OffsetDateTime,
+        }
+
+        let res = serde_json::from_str::<T>(r#"{"created":"2023-06-03 20:59:52.632 +00:00:00"}"#);
+        assert!(res.is_err());
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .starts_with(r#"invalid rfc3339 datetime "2023-06-03 20:59:52.632 +00:00:00": "#));
+    }
+}
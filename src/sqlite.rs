@@ -0,0 +1,168 @@
+use crate::storage::{from_row, last_per_id, storage_error};
+use crate::{CompressedWeb, Error, Storage};
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use std::path::Path;
+
+// SCHEMA creates the `web` table, see `sql/001_init.sql`.
+const SCHEMA: &str = include_str!("../sql/001_init.sql");
+
+// SqliteStorage is a `Storage` backed by the `web` table of a SQLite database. Each batch is
+// upserted in one transaction.
+#[derive(Clone, Debug)]
+pub struct SqliteStorage {
+    pub pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    // open opens the database at `path`, creating it and the `web` table if needed.
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let opts = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(opts)
+            .await
+            .map_err(storage_error)?;
+        Self::from_pool(pool).await
+    }
+
+    // from_pool uses an existing pool, creating the `web` table if needed.
+    pub async fn from_pool(pool: SqlitePool) -> Result<Self, Error> {
+        sqlx::query(SCHEMA)
+            .execute(&pool)
+            .await
+            .map_err(storage_error)?;
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for SqliteStorage {
+    async fn insert_batch(&self, webs: &[CompressedWeb]) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await.map_err(storage_error)?;
+        let mut inserted = 0;
+        for w in last_per_id(webs) {
+            inserted += sqlx::query(
+                "insert into web(id, created, url, status, response) values(?, ?, ?, ?, ?)
+                on conflict (id) do update set
+                    created = excluded.created,
+                    url = excluded.url,
+                    status = excluded.status,
+                    response = excluded.response
+                where (web.created, web.url, web.status, web.response)
+                    is not (excluded.created, excluded.url, excluded.status, excluded.response)",
+            )
+            .bind(w.id)
+            .bind(w.created)
+            .bind(&w.url)
+            .bind(w.status)
+            .bind(&w.response)
+            .execute(&mut tx)
+            .await
+            .map_err(storage_error)?
+            .rows_affected();
+        }
+        tx.commit().await.map_err(storage_error)?;
+        Ok(inserted)
+    }
+
+    async fn max_id(&self) -> Result<Option<i64>, Error> {
+        sqlx::query_scalar("select max(id) from web")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(storage_error)
+    }
+
+    async fn get(&self, id: i64) -> Result<Option<CompressedWeb>, Error> {
+        sqlx::query("select id, created, url, status, response from web where id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(storage_error)?
+            .map(|row| from_row(&row))
+            .transpose()
+    }
+
+    fn range(&self, min_wid: i64, max_wid: i64) -> BoxStream<'_, Result<CompressedWeb, Error>> {
+        sqlx::query(
+            "select id, created, url, status, response from web where id >= ? and id < ? order by id",
+        )
+        .bind(min_wid)
+        .bind(max_wid)
+        .fetch(&self.pool)
+        .map_err(storage_error)
+        .and_then(|row| async move { from_row(&row) })
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{compressed, temp_path};
+
+    async fn storage() -> SqliteStorage {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        SqliteStorage::from_pool(pool).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn insert_and_query() {
+        let s = storage().await;
+        assert_eq!(s.max_id().await.unwrap(), None);
+
+        assert_eq!(
+            s.insert_batch(&[compressed(100, "body"), compressed(102, "body")])
+                .await
+                .unwrap(),
+            2
+        );
+        // Re-inserting an unchanged entry is a no-op, a changed one replaces the stored entry
+        // and within a batch the last entry for an id wins.
+        let mut changed = compressed(102, "body");
+        changed.url = "https://example.com/changed".to_string();
+        let mut overwritten = compressed(102, "body");
+        overwritten.url = "https://example.com/overwritten".to_string();
+        let batch = [
+            compressed(100, "body"),
+            compressed(101, "body"),
+            overwritten,
+            changed.clone(),
+        ];
+        assert_eq!(s.insert_batch(&batch).await.unwrap(), 2);
+        assert_eq!(s.insert_batch(&batch).await.unwrap(), 0);
+
+        assert_eq!(s.max_id().await.unwrap(), Some(102));
+        assert_eq!(s.get(100).await.unwrap(), Some(compressed(100, "body")));
+        assert_eq!(s.get(102).await.unwrap(), Some(changed));
+        assert_eq!(s.get(103).await.unwrap(), None);
+
+        let ids = s
+            .range(101, 103)
+            .map_ok(|w| w.id)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(ids, [101, 102]);
+    }
+
+    #[tokio::test]
+    async fn open_creates_table() {
+        let path = temp_path("web.db");
+
+        let s = SqliteStorage::open(&path).await.unwrap();
+        s.insert_batch(&[compressed(100, "body")]).await.unwrap();
+        s.pool.close().await;
+
+        let s = SqliteStorage::open(&path).await.unwrap();
+        assert_eq!(s.get(100).await.unwrap(), Some(compressed(100, "body")));
+        s.pool.close().await;
+        std::fs::remove_file(&path).unwrap();
+    }
+}
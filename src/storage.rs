@@ -0,0 +1,65 @@
+use crate::{CompressedWeb, Error};
+use futures::stream::BoxStream;
+
+// Storage is a local replica of `CompressedWeb` entries, keyed by id.
+//
+// Inserting an id that is already stored replaces the stored entry and never fails, so a block
+// can safely be inserted again after a crash or a retried `Replicator` block, and an entry that
+// changed upstream is picked up when its range is pulled again.
+#[async_trait::async_trait]
+pub trait Storage: Send + Sync {
+    // insert_batch stores `webs` atomically, the last entry for an id winning, and returns how
+    // many ids were new or had a stored entry that differed. Inserting the same batch twice
+    // returns 0 the second time.
+    async fn insert_batch(&self, webs: &[CompressedWeb]) -> Result<u64, Error>;
+
+    // max_id returns the largest stored id, or `None` when the replica is empty.
+    async fn max_id(&self) -> Result<Option<i64>, Error>;
+
+    async fn get(&self, id: i64) -> Result<Option<CompressedWeb>, Error>;
+
+    // range yields the stored entries in the half-open range `[min_wid, max_wid)` in ascending
+    // id order.
+    fn range(&self, min_wid: i64, max_wid: i64) -> BoxStream<'_, Result<CompressedWeb, Error>>;
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub(crate) fn storage_error(e: sqlx::Error) -> Error {
+    Error::Storage(Box::new(e))
+}
+
+// from_row reads the `id, created, url, status, response` columns of a `web` table row.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub(crate) fn from_row<'r, R>(row: &'r R) -> Result<CompressedWeb, Error>
+where
+    R: sqlx::Row,
+    &'r str: sqlx::ColumnIndex<R>,
+    i64: sqlx::Decode<'r, R::Database> + sqlx::Type<R::Database>,
+    time::OffsetDateTime: sqlx::Decode<'r, R::Database> + sqlx::Type<R::Database>,
+    String: sqlx::Decode<'r, R::Database> + sqlx::Type<R::Database>,
+    i16: sqlx::Decode<'r, R::Database> + sqlx::Type<R::Database>,
+    Vec<u8>: sqlx::Decode<'r, R::Database> + sqlx::Type<R::Database>,
+{
+    Ok(CompressedWeb {
+        id: row.try_get("id").map_err(storage_error)?,
+        created: row.try_get("created").map_err(storage_error)?,
+        url: row.try_get("url").map_err(storage_error)?,
+        status: row.try_get("status").map_err(storage_error)?,
+        response: row.try_get("response").map_err(storage_error)?,
+    })
+}
+
+// last_per_id yields the entries of `webs` that no later entry with the same id overrides, in
+// batch order.
+#[cfg(any(feature = "sqlite", feature = "blob-store"))]
+pub(crate) fn last_per_id(webs: &[CompressedWeb]) -> impl Iterator<Item = &CompressedWeb> {
+    let last = webs
+        .iter()
+        .enumerate()
+        .map(|(i, w)| (w.id, i))
+        .collect::<std::collections::HashMap<_, _>>();
+    webs.iter()
+        .enumerate()
+        .filter(move |(i, w)| last[&w.id] == *i)
+        .map(|(_, w)| w)
+}
@@ -0,0 +1,37 @@
+use crate::Web;
+use std::path::PathBuf;
+
+// web returns an entry at `https://example.com/s/{id}/1` with `body` as its response.
+pub(crate) fn web(id: i64, body: &str) -> Web {
+    Web {
+        id,
+        created: time::macros::datetime!(2023-06-03 20:59:52.632 UTC),
+        url: format!("https://example.com/s/{id}/1"),
+        status: 200,
+        response: body.as_bytes().to_vec(),
+    }
+}
+
+// web_at returns an entry at `url` with `body {id}` as its response.
+pub(crate) fn web_at(id: i64, url: &str) -> Web {
+    Web {
+        url: url.to_string(),
+        ..web(id, &format!("body {id}"))
+    }
+}
+
+// compressed returns `web(id, body)` compressed.
+#[cfg(any(feature = "sqlite", feature = "postgres", feature = "blob-store"))]
+pub(crate) fn compressed(id: i64, body: &str) -> crate::CompressedWeb {
+    web(id, body).compress().unwrap()
+}
+
+// temp_path returns a path in the temp dir that is unique to this process and `name`, removing
+// whatever a previous run left there.
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("skitter-ro-client-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    let _ = std::fs::remove_file(&path);
+    path
+}
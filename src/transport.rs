@@ -0,0 +1,138 @@
+use crate::{Error, Url};
+use bytes::Bytes;
+use futures::stream::{self, BoxStream, Stream, StreamExt, TryStreamExt};
+use http::{HeaderMap, StatusCode};
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+// Request describes a single `GET` against the API. It is built by `proto` and sent by a
+// `Transport`.
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub url: Url,
+    pub headers: HeaderMap,
+    // timeout limits the request from sending it until its body has been read.
+    pub timeout: Option<Duration>,
+}
+
+// Response is the status, headers and streamed body returned by a `Transport`.
+#[derive(Debug)]
+pub struct Response {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Body,
+}
+
+// Body is a response body delivered as a stream of chunks.
+pub struct Body(BoxStream<'static, Result<Bytes, Error>>);
+
+impl Body {
+    pub fn empty() -> Self {
+        Self(stream::empty().boxed())
+    }
+
+    pub fn from_bytes(body: impl Into<Bytes>) -> Self {
+        Self(stream::once(futures::future::ready(Ok(body.into()))).boxed())
+    }
+
+    pub fn from_stream(body: impl Stream<Item = Result<Bytes, Error>> + Send + 'static) -> Self {
+        Self(body.boxed())
+    }
+
+    // bytes reads the whole body into memory.
+    pub async fn bytes(self) -> Result<Vec<u8>, Error> {
+        self.0
+            .try_fold(Vec::new(), |mut buf, chunk| async move {
+                buf.extend_from_slice(&chunk);
+                Ok(buf)
+            })
+            .await
+    }
+}
+
+impl Stream for Body {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Body")
+    }
+}
+
+// Transport sends a `Request` and returns the `Response` without interpreting it, so `Client`
+// can run on any http stack, an in-memory fake or a recorded response.
+//
+// Failing to send the request should be reported as `Error::Transport` and failing to read the
+// body as `Error::Body`. Non-200 responses are not errors at this level.
+#[async_trait::async_trait]
+pub trait Transport: fmt::Debug + Send + Sync {
+    async fn send(&self, req: Request) -> Result<Response, Error>;
+}
+
+#[async_trait::async_trait]
+impl<T: Transport + ?Sized> Transport for std::sync::Arc<T> {
+    async fn send(&self, req: Request) -> Result<Response, Error> {
+        (**self).send(req).await
+    }
+}
+
+// ReqwestTransport sends requests with a `reqwest::Client`. It is the default `Transport`.
+#[derive(Clone, Debug, Default)]
+pub struct ReqwestTransport {
+    pub client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for ReqwestTransport {
+    async fn send(&self, req: Request) -> Result<Response, Error> {
+        let mut builder = self.client.get(req.url).headers(req.headers);
+        if let Some(timeout) = req.timeout {
+            builder = builder.timeout(timeout);
+        }
+        let res = builder
+            .send()
+            .await
+            .map_err(|e| Error::Transport(Box::new(e)))?;
+
+        Ok(Response {
+            status: res.status(),
+            headers: res.headers().clone(),
+            body: Body::from_stream(res.bytes_stream().map_err(|e| Error::Body(Box::new(e)))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn body_bytes() {
+        let body = Body::from_stream(stream::iter([
+            Ok(Bytes::from_static(b"example ")),
+            Ok(Bytes::from_static(b"body")),
+        ]));
+        assert_eq!(body.bytes().await.unwrap(), b"example body");
+
+        assert!(Body::empty().bytes().await.unwrap().is_empty());
+
+        let body = Body::from_stream(stream::iter([
+            Ok(Bytes::from_static(b"example ")),
+            Err(Error::Body("connection reset".into())),
+        ]));
+        assert!(matches!(body.bytes().await, Err(Error::Body(_))));
+    }
+}
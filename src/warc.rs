@@ -0,0 +1,435 @@
+use crate::{Error, Web};
+use data_encoding::BASE32;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+#[cfg(feature = "tokio")]
+use futures::stream::{Stream, StreamExt};
+use http::StatusCode;
+use sha1::{Digest, Sha1};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use time::format_description::well_known::Rfc3339;
+use time::{OffsetDateTime, UtcOffset};
+
+// DEFAULT_MAX_FILE_SIZE is the size at which `WarcWriter` starts a new file.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1_000_000_000;
+
+// RecordType selects the WARC record written for each `Web`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RecordType {
+    // Response writes `response` records holding an HTTP response made of the entry's status,
+    // a `Content-Length` and optionally a `Content-Type` header, and its body. The API does not
+    // keep the original headers.
+    #[default]
+    Response,
+    // Resource writes `resource` records holding the body alone.
+    Resource,
+}
+
+// WarcWriter exports `Web` entries to gzipped WARC/1.1 files named
+// `<dir>/<prefix>-00000.warc.gz`, `<prefix>-00001.warc.gz` and so on.
+//
+// Every record is a separate gzip member, and every file starts with a `warcinfo` record that
+// the other records reference. Once a file reaches `max_file_size` the next record starts a new
+// file, so a file exceeds it by at most one record. Existing files are never overwritten: the
+// first file is numbered after the highest numbered `<prefix>-NNNNN.warc.gz` already in `dir`,
+// so a restarted export continues where the previous one stopped.
+//
+// Each record is written with a single `write` call, so all files are complete WARC files after
+// every `write`, even if the writer is dropped.
+#[derive(Debug)]
+pub struct WarcWriter {
+    dir: PathBuf,
+    prefix: String,
+    max_file_size: u64,
+    record_type: RecordType,
+    content_type: Option<String>,
+    files: Vec<PathBuf>,
+    // next_index is the number of the next file, found by `first_unused_index` when it is needed.
+    next_index: Option<u64>,
+    output: Option<Output>,
+}
+
+#[derive(Debug)]
+struct Output {
+    file: File,
+    len: u64,
+    warcinfo_id: String,
+}
+
+impl WarcWriter {
+    // new writes files into the existing directory `dir`. No file is created until the first
+    // record is written.
+    pub fn new(dir: impl Into<PathBuf>, prefix: impl Into<String>) -> Self {
+        Self {
+            dir: dir.into(),
+            prefix: prefix.into(),
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            record_type: RecordType::default(),
+            content_type: None,
+            files: vec![],
+            next_index: None,
+            output: None,
+        }
+    }
+
+    pub fn with_max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    pub fn with_record_type(mut self, record_type: RecordType) -> Self {
+        self.record_type = record_type;
+        self
+    }
+
+    // with_content_type sets the body's media type: the HTTP `Content-Type` header of
+    // `response` records, or the `Content-Type` of `resource` records, which is
+    // `application/octet-stream` otherwise.
+    pub fn with_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    // files returns the files created so far, in order.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    // write appends `w` as a single record.
+    pub fn write(&mut self, w: &Web) -> Result<(), Error> {
+        if w.url.is_empty() || w.url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(warc_error(format!(
+                "{}: invalid target uri {:?}",
+                w.id, w.url
+            )));
+        }
+        let date = warc_date(w.created)?;
+
+        let (warc_type, content_type, block, payload) = match self.record_type {
+            RecordType::Response => {
+                let status = u16::try_from(w.status)
+                    .ok()
+                    .and_then(|s| StatusCode::from_u16(s).ok())
+                    .ok_or_else(|| {
+                        warc_error(format!("{}: invalid http status {}", w.id, w.status))
+                    })?;
+                let mut block = format!(
+                    "HTTP/1.1 {} {}\r\n",
+                    status.as_str(),
+                    status.canonical_reason().unwrap_or("")
+                );
+                if let Some(content_type) = &self.content_type {
+                    block += &format!("Content-Type: {content_type}\r\n");
+                }
+                block += &format!("Content-Length: {}\r\n\r\n", w.response.len());
+                let mut block = block.into_bytes();
+                let payload = block.len();
+                block.extend_from_slice(&w.response);
+                (
+                    "response",
+                    "application/http;msgtype=response".to_string(),
+                    block,
+                    payload,
+                )
+            }
+            RecordType::Resource => {
+                let content_type = self
+                    .content_type
+                    .clone()
+                    .unwrap_or_else(|| "application/octet-stream".to_string());
+                ("resource", content_type, w.response.clone(), 0)
+            }
+        };
+
+        if self
+            .output
+            .as_ref()
+            .is_some_and(|o| o.len >= self.max_file_size)
+        {
+            self.output = None;
+        }
+        if self.output.is_none() {
+            self.output = Some(self.create()?);
+        }
+        let output = self.output.as_mut().expect("an output was just created");
+
+        let record = encode_record(
+            &[
+                ("WARC-Type", warc_type),
+                ("WARC-Record-ID", &record_id()),
+                ("WARC-Date", &date),
+                ("WARC-Target-URI", &w.url),
+                ("WARC-Warcinfo-ID", &output.warcinfo_id),
+                ("WARC-Block-Digest", &digest(&block)),
+                ("WARC-Payload-Digest", &digest(&block[payload..])),
+                ("Content-Type", &content_type),
+            ],
+            &block,
+        )?;
+        let path = self.files.last().expect("an output has a file");
+        output
+            .file
+            .write_all(&record)
+            .map_err(|e| io_error(path, e))?;
+        output.len += record.len() as u64;
+        Ok(())
+    }
+
+    // write_stream writes every entry of `webs` on tokio's blocking thread pool and returns the
+    // writer to continue with, or the first error.
+    #[cfg(feature = "tokio")]
+    pub async fn write_stream(
+        mut self,
+        webs: impl Stream<Item = Result<Web, Error>>,
+    ) -> Result<Self, Error> {
+        futures::pin_mut!(webs);
+        while let Some(w) = webs.next().await {
+            let w = w?;
+            let (writer, res) = crate::spawn_blocking(move || {
+                let res = self.write(&w);
+                (self, res)
+            })
+            .await
+            .map_err(|e| Error::Warc(Box::new(e)))?;
+            self = writer;
+            res?;
+        }
+        Ok(self)
+    }
+
+    // create creates the next file and writes its `warcinfo` record.
+    fn create(&mut self) -> Result<Output, Error> {
+        let index = match self.next_index {
+            Some(index) => index,
+            None => self.first_unused_index()?,
+        };
+        let name = format!("{}-{index:05}.warc.gz", self.prefix);
+        let path = self.dir.join(&name);
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| io_error(&path, e))?;
+
+        let warcinfo_id = record_id();
+        let fields = format!(
+            "software: skitter-ro-client/{}\r\n\
+            format: WARC File Format 1.1\r\n\
+            conformsTo: http://iipc.github.io/warc-specifications/specifications/warc-format/warc-1.1/\r\n",
+            env!("CARGO_PKG_VERSION")
+        );
+        let record = encode_record(
+            &[
+                ("WARC-Type", "warcinfo"),
+                ("WARC-Record-ID", &warcinfo_id),
+                ("WARC-Date", &warc_date(OffsetDateTime::now_utc())?),
+                ("WARC-Filename", &name),
+                ("Content-Type", "application/warc-fields"),
+            ],
+            fields.as_bytes(),
+        )?;
+        file.write_all(&record).map_err(|e| io_error(&path, e))?;
+
+        self.files.push(path);
+        self.next_index = Some(index + 1);
+        Ok(Output {
+            file,
+            len: record.len() as u64,
+            warcinfo_id,
+        })
+    }
+
+    // first_unused_index is one more than the highest index of the files in `dir` named like
+    // this writer's, or 0 if there are none.
+    fn first_unused_index(&self) -> Result<u64, Error> {
+        let mut next = 0;
+        for entry in std::fs::read_dir(&self.dir).map_err(|e| io_error(&self.dir, e))? {
+            let entry = entry.map_err(|e| io_error(&self.dir, e))?;
+            let index = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix(self.prefix.as_str()))
+                .and_then(|name| name.strip_prefix('-'))
+                .and_then(|name| name.strip_suffix(".warc.gz"))
+                .filter(|index| index.len() >= 5 && index.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|index| index.parse::<u64>().ok());
+            if let Some(index) = index {
+                next = next.max(index + 1);
+            }
+        }
+        Ok(next)
+    }
+}
+
+fn warc_error(msg: String) -> Error {
+    Error::Warc(msg.into())
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> Error {
+    warc_error(format!("{}: {e}", path.display()))
+}
+
+// warc_date formats `t` in UTC, keeping its fractional seconds as WARC/1.1 allows.
+fn warc_date(t: OffsetDateTime) -> Result<String, Error> {
+    t.to_offset(UtcOffset::UTC)
+        .format(&Rfc3339)
+        .map_err(|e| Error::Warc(Box::new(e)))
+}
+
+fn record_id() -> String {
+    format!("<urn:uuid:{}>", uuid::Uuid::new_v4())
+}
+
+// digest is the labelled base32 sha1 digest used by most WARC tools.
+fn digest(data: &[u8]) -> String {
+    format!("sha1:{}", BASE32.encode(&Sha1::digest(data)))
+}
+
+// encode_record returns a gzip member holding a record with `headers`, `Content-Length` and
+// `block`.
+fn encode_record(headers: &[(&str, &str)], block: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut record = b"WARC/1.1\r\n".to_vec();
+    for (name, value) in headers {
+        record.extend_from_slice(format!("{name}: {value}\r\n").as_bytes());
+    }
+    record.extend_from_slice(format!("Content-Length: {}\r\n\r\n", block.len()).as_bytes());
+    record.extend_from_slice(block);
+    record.extend_from_slice(b"\r\n\r\n");
+
+    let mut e = GzEncoder::new(vec![], Compression::default());
+    e.write_all(&record).map_err(Error::Compress)?;
+    e.finish().map_err(Error::Compress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_path, web};
+    use flate2::read::{GzDecoder, MultiGzDecoder};
+    use std::io::Read;
+
+    fn read_gz(path: &Path) -> String {
+        let mut s = String::new();
+        MultiGzDecoder::new(File::open(path).unwrap())
+            .read_to_string(&mut s)
+            .unwrap();
+        s
+    }
+
+    #[test]
+    fn write_and_rotate() {
+        let dir = temp_path("warc");
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut w = WarcWriter::new(&dir, "skitter")
+            .with_max_file_size(1)
+            .with_content_type("text/html");
+        // WARC-Date is written in UTC.
+        let mut first = web(100, "chapter 1");
+        first.created = time::macros::datetime!(2023-06-03 22:59:52.632 +2);
+        w.write(&first).unwrap();
+        w.write(&web(101, "ch 2")).unwrap();
+
+        let names = w
+            .files()
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["skitter-00000.warc.gz", "skitter-00001.warc.gz"]);
+
+        // The first gzip member is the warcinfo record alone.
+        let mut warcinfo = String::new();
+        GzDecoder::new(File::open(&w.files()[0]).unwrap())
+            .read_to_string(&mut warcinfo)
+            .unwrap();
+        assert!(warcinfo.starts_with("WARC/1.1\r\nWARC-Type: warcinfo\r\n"));
+        assert!(warcinfo.contains("WARC-Filename: skitter-00000.warc.gz\r\n"));
+        assert!(warcinfo.ends_with("\r\n\r\n"));
+        let warcinfo_id = warcinfo
+            .lines()
+            .find_map(|l| l.strip_prefix("WARC-Record-ID: "))
+            .unwrap();
+
+        let contents = read_gz(&w.files()[0]);
+        let response = &contents[warcinfo.len()..];
+        let block =
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 9\r\n\r\nchapter 1";
+        assert!(
+            response.starts_with("WARC/1.1\r\nWARC-Type: response\r\nWARC-Record-ID: <urn:uuid:")
+        );
+        for header in [
+            "WARC-Date: 2023-06-03T20:59:52.632Z".to_string(),
+            "WARC-Target-URI: https://example.com/s/100/1".to_string(),
+            format!("WARC-Warcinfo-ID: {warcinfo_id}"),
+            format!("WARC-Block-Digest: {}", digest(block.as_bytes())),
+            // sha1("chapter 1")
+            "WARC-Payload-Digest: sha1:5EZ23M5GDTHRHZE2XG2ZD4WP4KN2H7GU".to_string(),
+            "Content-Type: application/http;msgtype=response".to_string(),
+            format!("Content-Length: {}", block.len()),
+        ] {
+            assert!(response.contains(&format!("\r\n{header}\r\n")), "{header}");
+        }
+        assert!(response.ends_with(&format!("\r\n\r\n{block}\r\n\r\n")));
+
+        let contents = read_gz(&w.files()[1]);
+        assert!(contents.contains("WARC-Filename: skitter-00001.warc.gz\r\n"));
+        assert!(contents.contains("WARC-Target-URI: https://example.com/s/101/1\r\n"));
+        assert!(!contents.contains("/s/100/"));
+
+        // A new writer continues after the existing files instead of overwriting them.
+        std::fs::write(dir.join("skitter-00004.warc.gz.tmp"), "").unwrap();
+        std::fs::write(dir.join("other-00007.warc.gz"), "").unwrap();
+        let mut w = WarcWriter::new(&dir, "skitter");
+        w.write(&web(102, "ch 3")).unwrap();
+        assert_eq!(w.files(), [dir.join("skitter-00002.warc.gz")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn write_stream() {
+        let dir = temp_path("warc_stream");
+        std::fs::create_dir_all(&dir).unwrap();
+        let webs = futures::stream::iter([Ok(web(100, "chapter 1")), Ok(web(101, "ch 2"))]);
+        let w = WarcWriter::new(&dir, "skitter")
+            .write_stream(webs)
+            .await
+            .unwrap();
+
+        assert_eq!(w.files().len(), 1);
+        let contents = read_gz(&w.files()[0]);
+        assert_eq!(contents.matches("WARC-Type: response\r\n").count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resource_and_invalid_entries() {
+        let dir = temp_path("warc_resource");
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut w = WarcWriter::new(&dir, "skitter");
+
+        let mut failed = web(100, "");
+        failed.status = 0;
+        assert!(matches!(w.write(&failed), Err(Error::Warc(_))));
+        let mut injected = web(101, "chapter 1");
+        injected.url += "\r\nWARC-Type: revisit";
+        assert!(matches!(w.write(&injected), Err(Error::Warc(_))));
+        assert!(w.files().is_empty());
+
+        let mut w = w.with_record_type(RecordType::Resource);
+        w.write(&failed).unwrap();
+        w.write(&web(102, "chapter 1")).unwrap();
+        assert_eq!(w.files().len(), 1);
+
+        let contents = read_gz(&w.files()[0]);
+        assert_eq!(contents.matches("WARC-Type: resource\r\n").count(), 2);
+        assert!(contents.contains("Content-Type: application/octet-stream\r\n"));
+        assert!(contents.contains(
+            "WARC-Block-Digest: sha1:5EZ23M5GDTHRHZE2XG2ZD4WP4KN2H7GU\r\n\
+            WARC-Payload-Digest: sha1:5EZ23M5GDTHRHZE2XG2ZD4WP4KN2H7GU\r\n"
+        ));
+        assert!(contents.ends_with("Content-Length: 9\r\n\r\nchapter 1\r\n\r\n"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}